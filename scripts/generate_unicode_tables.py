@@ -0,0 +1,140 @@
+#!/usr/bin/env python3
+"""Regenerates the tables in src/unicode_tables from those in regex-syntax.
+
+regex-syntax ships tables that ucd-generate built from the Unicode Character
+Database. This script reads the ones for Unicode 16.0.0, from regex-syntax
+0.8.5, and writes them out in this crate's format:
+
+    cargo fetch   # or any build that downloads regex-syntax 0.8.5
+    python3 scripts/generate_unicode_tables.py \\
+        ~/.cargo/registry/src/index.crates.io-*/regex-syntax-0.8.5/src/unicode_tables
+
+The output directory defaults to src/unicode_tables. mod.rs and
+LICENSE-UNICODE are not generated.
+"""
+
+import os
+import re
+import sys
+
+HEADER = """\
+// Generated by scripts/generate_unicode_tables.py from the Unicode Character
+// Database, version 16.0.0, as tabulated by regex-syntax 0.8.5. Do not edit by hand.
+"""
+
+# The binary properties `\p{...}` accepts.
+BOOL_PROPERTIES = [
+    "Alphabetic", "ASCII_Hex_Digit", "Cased", "Dash", "Default_Ignorable_Code_Point",
+    "Diacritic", "Emoji", "Emoji_Component", "Emoji_Modifier", "Emoji_Modifier_Base",
+    "Emoji_Presentation", "Extended_Pictographic", "Extender", "Hex_Digit", "ID_Continue",
+    "ID_Start", "Ideographic", "Join_Control", "Lowercase", "Math", "Noncharacter_Code_Point",
+    "Pattern_Syntax", "Pattern_White_Space", "Quotation_Mark", "Regional_Indicator",
+    "Sentence_Terminal", "Terminal_Punctuation", "Uppercase", "Variation_Selector",
+    "White_Space", "XID_Continue", "XID_Start",
+]
+
+CHAR = r"'(?:\\u\{[0-9a-fA-F]+\}|\\.|[^'\\])'"
+ESCAPES = {"\\t": 9, "\\n": 10, "\\r": 13, "\\'": 39, "\\\\": 92, "\\0": 0}
+
+
+def parse_char(token):
+    body = token.strip()[1:-1]
+    if body.startswith("\\u{"):
+        return int(body[3:-1], 16)
+    if body.startswith("\\"):
+        return ESCAPES[body]
+    assert len(body) == 1, body
+    return ord(body)
+
+
+def fmt(c):
+    return "'\\u{%x}'" % c
+
+
+def read_tables(source, fname):
+    """The range tables in a regex-syntax file, and its BY_NAME index."""
+    src = open(os.path.join(source, fname)).read()
+    tables = {}
+    for name, body in re.findall(r"pub const ([A-Z0-9_]+): &'static \[\(char, char\)\] =\s*&\[(.*?)\];", src, re.S):
+        tables[name] = [(parse_char(a), parse_char(b)) for a, b in re.findall(r"\((" + CHAR + r"),\s*(" + CHAR + r")\)", body)]
+
+    by_name = {}
+    index = re.search(r"pub const BY_NAME: .*?= *&\[(.*?)\];", src, re.S)
+    if index:
+        by_name = dict(re.findall(r'\("([^"]+)",\s*([A-Z0-9_]+)\)', index.group(1)))
+
+    return tables, by_name
+
+
+def emit_table(name, ranges, per_line=3):
+    lines = [f"pub(crate) const {name}: &[(char, char)] = &["]
+    for i in range(0, len(ranges), per_line):
+        lines.append("    " + " ".join(f"({fmt(a)}, {fmt(b)})," for a, b in ranges[i:i + per_line]))
+    lines.append("];")
+    return "\n".join(lines)
+
+
+def value_aliases(source, property):
+    src = open(os.path.join(source, "property_values.rs")).read()
+    for name, body in re.findall(r'\(\s*"([^"]+)",\s*&\[(.*?)\],\s*\)', src, re.S):
+        if name == property:
+            return re.findall(r'\("([^"]+)", "([^"]+)"\)', body)
+    raise KeyError(property)
+
+
+def write_property(source, output, fname, names=None, aliases=()):
+    tables, by_name = read_tables(source, fname)
+    if names is not None:
+        by_name = {name: by_name[name] for name in names}
+
+    parts = [HEADER]
+    parts.append("pub(crate) const BY_NAME: &[(&str, &[(char, char)])] = &[")
+    parts += [f'    ("{name}", {by_name[name]}),' for name in sorted(by_name)]
+    parts.append("];\n")
+    parts.append("/// Loosely matched aliases (lowercase, no spaces, hyphens or underscores) to canonical names.")
+    parts.append("pub(crate) const ALIASES: &[(&str, &str)] = &[")
+    parts += [f'    ("{alias}", "{name}"),' for alias, name in sorted(aliases) if name in by_name]
+    parts.append("];\n")
+    parts += [emit_table(by_name[name], tables[by_name[name]]) + "\n" for name in sorted(by_name)]
+
+    open(os.path.join(output, fname), "w").write("\n".join(parts))
+
+
+def write_perl_word(source, output):
+    tables, _ = read_tables(source, "perl_word.rs")
+    open(os.path.join(output, "perl_word.rs"), "w").write(HEADER + "\n" + emit_table("PERL_WORD", tables["PERL_WORD"]) + "\n")
+
+
+def write_case_folding(source, output):
+    src = open(os.path.join(source, "case_folding_simple.rs")).read()
+    body = re.search(r"CASE_FOLDING_SIMPLE: .*?= *&\[(.*)\];", src, re.S).group(1)
+    entries = [
+        (parse_char(key), [parse_char(c) for c in re.findall(CHAR, rest)])
+        for key, rest in re.findall(r"\((" + CHAR + r"),\s*&\[(.*?)\]\)", body)
+    ]
+    assert entries == sorted(entries)
+
+    lines = ["pub(crate) const CASE_FOLDING_SIMPLE: &[(char, &[char])] = &["]
+    lines += [f"    ({fmt(key)}, &[{', '.join(fmt(c) for c in orbit)}])," for key, orbit in entries]
+    lines.append("];")
+    open(os.path.join(output, "case_folding_simple.rs"), "w").write(HEADER + "\n" + "\n".join(lines) + "\n")
+
+
+def main():
+    if len(sys.argv) not in (2, 3):
+        sys.exit(__doc__)
+
+    source = sys.argv[1]
+    output = sys.argv[2] if len(sys.argv) == 3 else os.path.join(os.path.dirname(__file__), "..", "src", "unicode_tables")
+    property_names = open(os.path.join(source, "property_names.rs")).read()
+
+    write_property(source, output, "general_category.rs", aliases=value_aliases(source, "General_Category"))
+    write_property(source, output, "script.rs", aliases=value_aliases(source, "Script"))
+    write_property(source, output, "property_bool.rs", names=BOOL_PROPERTIES,
+                   aliases=re.findall(r'\("([^"]+)", "([^"]+)"\)', property_names))
+    write_perl_word(source, output)
+    write_case_folding(source, output)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use crate::errors::MatchError;
+use crate::look::{Context, Look, LookTables};
+use crate::matcher::Slots;
+use crate::nfa::{NFA, StateID, Transition};
+use crate::unicode;
+
+// Work left on the backtracking stack. Jobs are popped in priority order, and
+// undo jobs sit below the alternatives they were pushed for, so state is put
+// back once every path through them has failed.
+enum Job {
+    Explore { state: StateID, position: usize },
+    Take { state: StateID, index: usize, position: usize },
+    RestoreSlot { slot: usize, value: Option<usize> },
+    RestoreSlots(Slots),
+    Unmark { state: StateID, position: usize },
+}
+
+/// A depth-first search over the NFA that tries transitions in priority
+/// order, so the first accepting path it finds is the leftmost-first match.
+/// Unlike the automaton simulations it can follow backreferences, at the
+/// cost of time exponential in the pattern, so every search is cut off after
+/// `limit` steps. Look-arounds and atomic groups are searched for on the
+/// spot, sharing the same budget.
+pub(crate) struct Backtracker<'n> {
+    nfa: &'n NFA,
+    limit: usize,
+    steps: usize,
+}
+
+impl<'n> Backtracker<'n> {
+    pub(crate) fn new(nfa: &'n NFA, limit: usize) -> Self {
+        Backtracker { nfa, limit, steps: 0 }
+    }
+
+    /// Finds the leftmost-first match starting at or after byte offset
+    /// `start`, as `Matcher::captures_at` does. With `full` set, the match
+    /// must instead start at `start` and run to the end of `input`.
+    pub(crate) fn captures_at(
+        &mut self,
+        input: &str,
+        start: usize,
+        slot_count: usize,
+        full: bool,
+    ) -> Result<Option<Slots>, MatchError> {
+        let starts = input[start..]
+            .char_indices()
+            .map(|(offset, _)| start + offset)
+            .chain([input.len()]);
+
+        let nfa = self.nfa;
+        let end = full.then_some(input.len());
+
+        for position in starts {
+            if let Some((mut slots, _)) = self.attempt(nfa, input, position, end, vec![None; 2 * nfa.group_count])? {
+                slots.truncate(slot_count);
+                return Ok(Some(slots))
+            }
+
+            if full { break }
+        }
+
+        Ok(None)
+    }
+
+    // Searches `nfa` for a match starting exactly at `start`, and if `end` is
+    // given, ending exactly there. Returns the slots and where the match ends.
+    fn attempt(
+        &mut self,
+        nfa: &NFA,
+        input: &str,
+        start: usize,
+        end: Option<usize>,
+        mut slots: Slots,
+    ) -> Result<Option<(Slots, usize)>, MatchError> {
+        // States reached at a position without consuming anything since, so
+        // that loops which match the empty string are not followed forever.
+        let mut on_path = HashSet::new();
+        let mut stack = vec![Job::Explore { state: nfa.start_state, position: start }];
+
+        while let Some(job) = stack.pop() {
+            match job {
+                Job::Explore { state, position } => {
+                    self.steps += 1;
+                    if self.steps > self.limit { return Err(MatchError::BacktrackLimitExceeded(self.limit)) }
+
+                    if !on_path.insert((state, position)) { continue }
+                    stack.push(Job::Unmark { state, position });
+
+                    if nfa.end_states.contains(&state) && end.is_none_or(|end| position == end) {
+                        return Ok(Some((slots, position)))
+                    }
+
+                    let count = nfa.transitions.get(&state).map_or(0, Vec::len);
+                    for index in (0..count).rev() {
+                        stack.push(Job::Take { state, index, position });
+                    }
+                },
+                Job::Take { state, index, position } => {
+                    let (transition, next_state) = &nfa.transitions[&state][index];
+
+                    if let Transition::Save(slot) = transition && let Some(value) = slots.get_mut(*slot) {
+                        stack.push(Job::RestoreSlot { slot: *slot, value: *value });
+                        *value = Some(position);
+                    }
+
+                    if let Transition::Atomic(atomic) = transition {
+                        // Only the first way the group matches is ever tried
+                        let Some((group_slots, next_position)) = self.attempt(&atomic.nfa, input, position, None, slots.clone())? else { continue };
+                        stack.push(Job::RestoreSlots(std::mem::replace(&mut slots, group_slots)));
+                        stack.push(Job::Explore { state: *next_state, position: next_position });
+                        continue
+                    }
+
+                    if let Some(next_position) = self.step(transition, input, position, &slots)? {
+                        stack.push(Job::Explore { state: *next_state, position: next_position });
+                    }
+                },
+                Job::RestoreSlot { slot, value } => slots[slot] = value,
+                Job::RestoreSlots(saved) => slots = saved,
+                Job::Unmark { state, position } => { on_path.remove(&(state, position)); },
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Where taking `transition` at `position` leads, or None if it can't be taken.
+    fn step(&mut self, transition: &Transition, input: &str, position: usize, slots: &Slots) -> Result<Option<usize>, MatchError> {
+        match transition {
+            &Transition::Backref { group, case_insensitive } => Ok(backref(input, position, slots, group, case_insensitive)),
+            Transition::Look(look) => match self.look_matches(look, input, position, slots)? != look.negated {
+                true => Ok(Some(position)),
+                false => Ok(None),
+            },
+            transition => {
+                let no_looks = LookTables::default();
+                let context = Context::new(input, position, &no_looks);
+
+                match transition.epsilon_at(&context) {
+                    true => Ok(Some(position)),
+                    false => Ok(context.next.filter(|&ch| transition.matches(ch)).map(|ch| position + ch.len_utf8())),
+                }
+            },
+        }
+    }
+
+    // Whether the sub-pattern of `look` matches starting at `position`, for a
+    // lookahead, or ending there, for a lookbehind. Groups set inside it are
+    // discarded, but backreferences in it can see the groups set before it.
+    fn look_matches(&mut self, look: &Look, input: &str, position: usize, slots: &Slots) -> Result<bool, MatchError> {
+        if look.ahead {
+            return Ok(self.attempt(&look.nfa, input, position, None, slots.clone())?.is_some())
+        }
+
+        let starts = input[..position].char_indices().map(|(start, _)| start).chain([position]);
+
+        for start in starts {
+            if self.attempt(&look.nfa, input, start, Some(position), slots.clone())?.is_some() {
+                return Ok(true)
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+// Where matching the text of `group` again at `position` ends, if it does.
+fn backref(input: &str, position: usize, slots: &Slots, group: usize, case_insensitive: bool) -> Option<usize> {
+    let captured = &input[(*slots.get(2 * group)?)?..(*slots.get(2 * group + 1)?)?];
+    let mut rest = input[position..].chars();
+    let mut length = 0;
+
+    for expected in captured.chars() {
+        let ch = rest.next()?;
+        let equal = ch == expected ||
+                    case_insensitive && unicode::simple_folds(expected, expected).any(|c| c == ch);
+        if !equal { return None }
+        length += ch.len_utf8();
+    }
+
+    Some(position + length)
+}
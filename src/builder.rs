@@ -0,0 +1,159 @@
+use crate::{
+    Lexer,
+    Parser,
+    Matcher,
+    RRegex,
+    errors::ParseError,
+    token::Flags,
+};
+
+/// Upper bound on the number of NFA states a compiled pattern may use.
+pub const DEFAULT_SIZE_LIMIT: usize = 10_000;
+
+/// Upper bound on the number of steps a single backtracking search may take.
+pub const DEFAULT_BACKTRACK_LIMIT: usize = 1_000_000;
+
+/// Which match `find` and `captures` report when several start at the same
+/// position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchKind {
+    /// The first match in priority order, as in Perl: alternatives are tried
+    /// left to right and quantifiers take as much as they can, or as little
+    /// if lazy. This is the default.
+    #[default]
+    LeftmostFirst,
+    /// The longest match, as in POSIX, with groups following the POSIX
+    /// submatch rules.
+    LeftmostLongest,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub(crate) size_limit: usize,
+    pub(crate) dot_matches_new_line: bool,
+    pub(crate) unicode: bool,
+    pub(crate) multi_line: bool,
+    pub(crate) case_insensitive: bool,
+    pub(crate) ignore_whitespace: bool,
+    pub(crate) backtrack_limit: usize,
+    pub(crate) match_kind: MatchKind,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            size_limit: DEFAULT_SIZE_LIMIT,
+            dot_matches_new_line: false,
+            unicode: true,
+            multi_line: false,
+            case_insensitive: false,
+            ignore_whitespace: false,
+            backtrack_limit: DEFAULT_BACKTRACK_LIMIT,
+            match_kind: MatchKind::LeftmostFirst,
+        }
+    }
+}
+
+impl Config {
+    // Applies the flags of an inline group, e.g. `(?m-s)`, on top of this
+    // configuration.
+    pub(crate) fn apply(&mut self, flags: &Flags) {
+        if let Some(multi_line) = flags.multi_line { self.multi_line = multi_line }
+        if let Some(dot_matches_new_line) = flags.dot_matches_new_line { self.dot_matches_new_line = dot_matches_new_line }
+        if let Some(case_insensitive) = flags.case_insensitive { self.case_insensitive = case_insensitive }
+        if let Some(ignore_whitespace) = flags.ignore_whitespace { self.ignore_whitespace = ignore_whitespace }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RRegexBuilder {
+    pattern: String,
+    config: Config,
+}
+
+impl RRegexBuilder {
+    pub fn new(pattern: &str) -> Self {
+        RRegexBuilder {
+            pattern: pattern.to_string(),
+            config: Config::default(),
+        }
+    }
+
+    /// Sets the maximum number of NFA states the pattern may expand to.
+    /// Counted repetitions such as `a{1000}` copy their operand, so this
+    /// bounds how much memory a single pattern can ask for.
+    pub fn size_limit(&mut self, limit: usize) -> &mut Self {
+        self.config.size_limit = limit;
+        self
+    }
+
+    /// When enabled, `.` also matches `\n`. It is disabled by default.
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut Self {
+        self.config.dot_matches_new_line = yes;
+        self
+    }
+
+    /// When enabled (the default), `\d`, `\w` and `\s` use their Unicode
+    /// definitions. When disabled they only match ASCII characters, and `\b`
+    /// and `\B` only treat ASCII letters, digits and `_` as word characters.
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.config.unicode = yes;
+        self
+    }
+
+    /// When enabled, `^` and `$` also match just after and just before a `\n`,
+    /// not only at the start and end of the input. `\A` and `\z` are unaffected.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.config.multi_line = yes;
+        self
+    }
+
+    /// When enabled, letters match all of their case variants under Unicode
+    /// simple case folding, so `k` also matches `K` and the Kelvin sign U+212A.
+    /// With `unicode` disabled, only ASCII letters are folded. It is disabled
+    /// by default, and `(?i)` enables it within a pattern.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.config.case_insensitive = yes;
+        self
+    }
+
+    /// When enabled, unescaped whitespace outside classes is ignored and `#`
+    /// starts a comment that runs to the end of the line, so long patterns
+    /// can be laid out over several lines. Use `\ ` or `\#` to match those
+    /// characters. It is disabled by default, and `(?x)` enables it within a
+    /// pattern.
+    pub fn ignore_whitespace(&mut self, yes: bool) -> &mut Self {
+        self.config.ignore_whitespace = yes;
+        self
+    }
+
+    /// Sets how many steps the backtracking engine may take in one search.
+    /// Only patterns with backreferences use it, and a search that runs out
+    /// reports `MatchError::BacktrackLimitExceeded`.
+    pub fn backtrack_limit(&mut self, limit: usize) -> &mut Self {
+        self.config.backtrack_limit = limit;
+        self
+    }
+
+    /// Chooses between leftmost-first (the default) and leftmost-longest
+    /// matching. With leftmost-longest, `a|ab` finds `ab` in "ab", and each
+    /// group, from left to right, captures the longest text it can while the
+    /// whole match stays the same, so `(a|ab)(c|bcd)(d*)` captures `ab`, `c`
+    /// and `d` in "abcd". A group inside a repetition reports its last
+    /// iteration.
+    /// Lazy quantifiers behave as greedy ones, and patterns with
+    /// backreferences, atomic groups or possessive quantifiers are rejected.
+    pub fn match_kind(&mut self, kind: MatchKind) -> &mut Self {
+        self.config.match_kind = kind;
+        self
+    }
+
+    pub fn build(&self) -> Result<RRegex, ParseError> {
+        let mut lexer = Lexer::with_config(self.pattern.clone(), self.config.clone());
+        let mut parser = Parser::with_config(&mut lexer, self.config.clone())?;
+        let nfa = parser.parse()?;
+        let matcher = Matcher::with_config(nfa, &self.config);
+
+        Ok(RRegex::from_parts(matcher, parser.group_names().to_vec()))
+    }
+}
@@ -0,0 +1,201 @@
+use crate::unicode;
+use crate::unicode_tables::{general_category, perl_word, property_bool};
+
+/// A set of Unicode scalar values stored as sorted, non-overlapping,
+/// non-adjacent inclusive ranges.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CharClass {
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    pub fn new(ranges: Vec<(char, char)>) -> Self {
+        let mut class = CharClass { ranges };
+        class.canonicalize();
+        class
+    }
+
+    pub fn from_table(table: &[(char, char)]) -> Self {
+        CharClass::new(table.to_vec())
+    }
+
+    pub fn empty() -> Self {
+        CharClass { ranges: Vec::new() }
+    }
+
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        ranges_contain(&self.ranges, c)
+    }
+
+    pub fn push(&mut self, start: char, end: char) {
+        self.ranges.push((start, end));
+        self.canonicalize();
+    }
+
+    pub fn union(&mut self, other: &CharClass) {
+        self.ranges.extend_from_slice(&other.ranges);
+        self.canonicalize();
+    }
+
+    pub fn intersect(&self, other: &CharClass) -> Self {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while let (Some(&(a_start, a_end)), Some(&(b_start, b_end))) = (self.ranges.get(i), other.ranges.get(j)) {
+            let start = a_start.max(b_start);
+            let end = a_end.min(b_end);
+
+            if start <= end { ranges.push((start, end)); }
+
+            // Advance whichever range finishes first; the other may still overlap the next one.
+            if a_end < b_end { i += 1 } else { j += 1 }
+        }
+
+        CharClass { ranges }
+    }
+
+    pub fn difference(&self, other: &CharClass) -> Self {
+        self.intersect(&other.negate())
+    }
+
+    pub fn symmetric_difference(&self, other: &CharClass) -> Self {
+        let mut union = self.clone();
+        union.union(other);
+        union.difference(&self.intersect(other))
+    }
+
+    /// Closes the class under simple case folding, so that it holds every
+    /// case variant of its characters, e.g. 'K' and the Kelvin sign for 'k'.
+    pub fn case_fold(&self) -> Self {
+        let mut ranges = self.ranges.clone();
+
+        for &(start, end) in &self.ranges {
+            ranges.extend(unicode::simple_folds(start, end).map(|c| (c, c)));
+        }
+
+        CharClass::new(ranges)
+    }
+
+    pub fn negate(&self) -> Self {
+        let mut ranges = Vec::new();
+        let mut next = Some('\0');
+
+        for &(start, end) in &self.ranges {
+            if let Some(lower) = next.filter(|&lower| lower < start) {
+                ranges.push((lower, decrement(start)));
+            }
+            next = increment(end);
+        }
+
+        if let Some(lower) = next {
+            ranges.push((lower, char::MAX));
+        }
+
+        CharClass { ranges }
+    }
+
+    /// The class behind a Perl shorthand: `d`, `w` or `s`, with uppercase letters
+    /// giving the negation. Unicode mode uses the Unicode definitions, otherwise
+    /// only ASCII characters are members.
+    pub fn perl(name: char, unicode: bool) -> Self {
+        let class = match (name.to_ascii_lowercase(), unicode) {
+            ('d', true) => CharClass::from_table(general_category::DECIMAL_NUMBER),
+            ('w', true) => CharClass::from_table(perl_word::PERL_WORD),
+            ('s', true) => CharClass::from_table(property_bool::WHITE_SPACE),
+            ('d', false) => CharClass::new(vec![('0', '9')]),
+            ('w', false) => CharClass::new(vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]),
+            ('s', false) => CharClass::new(vec![('\t', '\r'), (' ', ' ')]),
+            _ => unreachable!("not a Perl class: {name}"),
+        };
+
+        match name.is_ascii_uppercase() {
+            true => class.negate(),
+            false => class,
+        }
+    }
+
+    /// The ASCII class behind a POSIX bracket name such as `alpha` in `[[:alpha:]]`.
+    pub fn posix(name: &str) -> Option<Self> {
+        let ranges = match name {
+            "alnum" => vec![('0', '9'), ('A', 'Z'), ('a', 'z')],
+            "alpha" => vec![('A', 'Z'), ('a', 'z')],
+            "blank" => vec![('\t', '\t'), (' ', ' ')],
+            "cntrl" => vec![('\0', '\x1F'), ('\x7F', '\x7F')],
+            "digit" => vec![('0', '9')],
+            "graph" => vec![('!', '~')],
+            "lower" => vec![('a', 'z')],
+            "print" => vec![(' ', '~')],
+            "punct" => vec![('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+            "space" => vec![('\t', '\r'), (' ', ' ')],
+            "upper" => vec![('A', 'Z')],
+            "xdigit" => vec![('0', '9'), ('A', 'F'), ('a', 'f')],
+            _ => return None,
+        };
+
+        Some(CharClass::new(ranges))
+    }
+
+    fn canonicalize(&mut self) {
+        self.ranges.sort_unstable();
+
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(self.ranges.len());
+
+        for &(start, end) in &self.ranges {
+            match merged.last_mut() {
+                Some(last) if increment(last.1).is_none_or(|next| start <= next) => {
+                    last.1 = last.1.max(end);
+                },
+                _ => merged.push((start, end)),
+            }
+        }
+
+        self.ranges = merged;
+    }
+}
+
+/// Binary search over sorted, non-overlapping ranges.
+pub(crate) fn ranges_contain(ranges: &[(char, char)], c: char) -> bool {
+    ranges
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                std::cmp::Ordering::Less
+            } else if start > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+// Steps over the surrogate gap, which holds no scalar values.
+fn increment(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+fn decrement(c: char) -> char {
+    match c {
+        '\u{E000}' => '\u{D7FF}',
+        c => char::from_u32(c as u32 - 1).expect("decrement below the first range"),
+    }
+}
+
+impl std::fmt::Display for CharClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "[")?;
+        for &(start, end) in &self.ranges {
+            match start == end {
+                true => write!(f, "{}", start.escape_debug())?,
+                false => write!(f, "{}-{}", start.escape_debug(), end.escape_debug())?,
+            }
+        }
+        write!(f, "]")
+    }
+}
@@ -1,11 +1,42 @@
 use crate::token::Token;
 
+/// Byte offsets into the pattern, `start` inclusive and `end` exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
 
 #[derive(Debug)]
 pub enum ParseError{
     UnexpectedToken(Token),
     MismatchedParentheses,
     UnexpectedEOF,
+    InvalidRepetition,
+    SizeLimitExceeded(usize),
+    UnclosedClass,
+    InvalidClassRange(char, char),
+    InvalidEscape(char),
+    TrailingBackslash,
+    InvalidHexEscape(Span),
+    InvalidCodePoint(Span),
+    UnknownUnicodeProperty(String),
+    MissingPropertyName,
+    UnknownPosixClass(String),
+    InvalidGroupName(String),
+    DuplicateGroupName(String),
+    UnknownFlag(char),
+    InvalidFlags(Span),
+    InvalidBackreference(usize),
+    UnknownGroupName(String),
+    UnsupportedAtomicGroup,
+    UnsupportedWithLeftmostLongest(&'static str),
 }
 
 impl std::fmt::Display for ParseError {
@@ -14,6 +45,41 @@ impl std::fmt::Display for ParseError {
             ParseError::UnexpectedToken(t) => write!(f, "Unexpected Token: {}", t),
             ParseError::MismatchedParentheses => write!(f, "Uneven number of parentheses."),
             ParseError::UnexpectedEOF => write!(f, "Unexpected EOF."),
+            ParseError::InvalidRepetition => write!(f, "Invalid counted repetition."),
+            ParseError::SizeLimitExceeded(limit) => write!(f, "Pattern exceeds the limit of {limit} NFA states."),
+            ParseError::UnclosedClass => write!(f, "Unclosed character class."),
+            ParseError::InvalidClassRange(start, end) => write!(f, "Invalid class range: {start}-{end}"),
+            ParseError::InvalidEscape(c) => write!(f, "Invalid escape sequence: \\{c}"),
+            ParseError::TrailingBackslash => write!(f, "Pattern ends with an unescaped backslash."),
+            ParseError::InvalidHexEscape(span) => write!(f, "Invalid hex escape at {span}."),
+            ParseError::InvalidCodePoint(span) => write!(f, "Escape at {span} is not a Unicode scalar value."),
+            ParseError::UnknownUnicodeProperty(name) => write!(f, "Unknown Unicode property: {name}"),
+            ParseError::MissingPropertyName => write!(f, "Expected a Unicode property name after \\p or \\P."),
+            ParseError::UnknownPosixClass(name) => write!(f, "Unknown POSIX class: {name}"),
+            ParseError::InvalidGroupName(name) => write!(f, "Invalid capture group name: {name}"),
+            ParseError::DuplicateGroupName(name) => write!(f, "Duplicate capture group name: {name}"),
+            ParseError::UnknownFlag(c) => write!(f, "Unknown flag: {c}"),
+            ParseError::InvalidFlags(span) => write!(f, "Malformed flag group at {span}."),
+            ParseError::InvalidBackreference(group) => write!(f, "Backreference to a group that hasn't been opened: \\{group}"),
+            ParseError::UnknownGroupName(name) => write!(f, "Backreference to an unknown group: {name}"),
+            ParseError::UnsupportedAtomicGroup => write!(f, "Atomic group or possessive quantifier could change the match here, which is only supported in patterns with backreferences."),
+            ParseError::UnsupportedWithLeftmostLongest(feature) => write!(f, "{feature} are not supported with leftmost-longest matching."),
+        }
+    }
+}
+
+/// An error from a search that couldn't be completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchError {
+    /// The backtracking engine, used for patterns with backreferences, took
+    /// more steps than its limit allows.
+    BacktrackLimitExceeded(usize),
+}
+
+impl std::fmt::Display for MatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            MatchError::BacktrackLimitExceeded(limit) => write!(f, "Search gave up after {limit} backtracking steps."),
         }
     }
 }
\ No newline at end of file
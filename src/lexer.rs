@@ -1,5 +1,8 @@
-use crate::token::Token;
-use crate::errors::ParseError;
+use crate::token::{Token, Flags};
+use crate::errors::{ParseError, Span};
+use crate::class::CharClass;
+use crate::builder::Config;
+use crate::unicode;
 
 #[derive(Debug)]
 enum LexerState{
@@ -10,14 +13,26 @@ enum LexerState{
 #[derive(Debug)]
 pub struct Lexer{
     input: String,
+    length: usize,
     state: LexerState,
+    config: Config,
+    // Configurations of the enclosing groups, restored at each ')', so that
+    // inline flags only last until the end of the group that sets them.
+    scopes: Vec<Config>,
 }
 
 impl Lexer{
     pub fn new(input: String) -> Self {
+        Lexer::with_config(input, Config::default())
+    }
+
+    pub(crate) fn with_config(input: String, config: Config) -> Self {
         Lexer {
+            length: input.len(),
             input: input.chars().rev().collect::<String>(),
             state: LexerState::Empty,
+            config,
+            scopes: Vec::new(),
         }
     }
 
@@ -33,14 +48,15 @@ impl Lexer{
                 };
 
                 match &self.state {
-                    LexerState::Peeked(t) => Some(Ok(*t)),
+                    LexerState::Peeked(t) => Some(Ok(t.clone())),
                     _ => None,
                 }
             },
-            LexerState::Peeked(t) => Some(Ok(*t)),
+            LexerState::Peeked(t) => Some(Ok(t.clone())),
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Result<Token, ParseError>> {
         match std::mem::replace(&mut self.state, LexerState::Empty) {
             LexerState::Empty => self.next_token(),
@@ -52,18 +68,461 @@ impl Lexer{
     }
 
     pub fn next_token(&mut self) -> Option<Result<Token, ParseError>> {
+        self.skip_ignored();
         if self.input.is_empty() { return None }
 
         let cur_char = self.input.pop();
 
-        match cur_char {
-            Some(c) if c.is_alphabetic() => Some(Ok(Token::Literal(c))),
+        let token = match cur_char {
+            Some('\\') => Some(self.lex_escape()),
+            Some('.') => Some(Ok(Token::Dot)),
+            Some('^') => Some(Ok(Token::Caret)),
+            Some('$') => Some(Ok(Token::Dollar)),
             Some('|') => Some(Ok(Token::Union)),
             Some('*') => Some(Ok(Token::Star)),
-            Some('(') => Some(Ok(Token::LParen)),
+            Some('+') => Some(Ok(Token::Plus)),
+            Some('?') => Some(Ok(Token::Question)),
+            Some('{') => Some(self.lex_repetition()),
+            Some('[') => Some(self.lex_class()),
+            Some('(') => Some(self.lex_group()),
             Some(')') => Some(Ok(Token::RParen)),
+            Some(c) if !c.is_control() || c.is_whitespace() => Some(Ok(Token::Literal(c))),
             None => None,
             c => Some(Err(ParseError::UnexpectedToken(Token::Unknown(c?)))),
+        };
+
+        token.map(|token| Ok(self.apply_scope(token?)))
+    }
+
+    // Tracks the flags in effect as groups open and close, and expands
+    // literals to their case variants when case-insensitive.
+    fn apply_scope(&mut self, token: Token) -> Token {
+        match &token {
+            Token::LParen | Token::NamedGroup(_) | Token::Look { .. } | Token::Atomic => self.scopes.push(self.config.clone()),
+            Token::NonCapturing(flags) => {
+                self.scopes.push(self.config.clone());
+                self.config.apply(flags);
+            },
+            Token::SetFlags(flags) => self.config.apply(flags),
+            Token::RParen => if let Some(outer) = self.scopes.pop() { self.config = outer },
+            &Token::Literal(c) if self.config.case_insensitive => {
+                let class = self.case_fold(CharClass::new(vec![(c, c)]));
+                if class.ranges() != [(c, c)] { return Token::Class(class) }
+            },
+            _ => {},
+        }
+
+        token
+    }
+
+    // Adds the case variants of the class's characters, keeping to ASCII
+    // ones when Unicode is disabled.
+    fn case_fold(&self, class: CharClass) -> CharClass {
+        let mut folded = class.case_fold();
+        if !self.config.unicode {
+            folded = folded.intersect(&CharClass::new(vec![('\0', '\x7F')]));
+            folded.union(&class);
+        }
+        folded
+    }
+
+    // In extended mode, skips whitespace and `#` comments up to the next
+    // character that means something.
+    fn skip_ignored(&mut self) {
+        if !self.config.ignore_whitespace { return }
+
+        loop {
+            match self.peek_char() {
+                Some(c) if c.is_whitespace() => { self.input.pop(); },
+                Some('#') => while !matches!(self.input.pop(), Some('\n') | None) {},
+                _ => break,
+            }
+        }
+    }
+
+    // Byte offset of the next unread character in the original pattern.
+    fn position(&self) -> usize {
+        self.length - self.input.len()
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input.chars().next_back()
+    }
+
+    fn peek_second_char(&self) -> Option<char> {
+        self.input.chars().nth_back(1)
+    }
+
+    // Lexes what follows an opening '('. `(?=`, `(?!`, `(?<=` and `(?<!` open
+    // a look-around, `(?>` an atomic group, `(?P<name>` and `(?<name>` a named group, `(?flags:` a
+    // non-capturing group, and `(?flags)` sets flags for the rest of the
+    // enclosing group. Anything else is a plain parenthesis.
+    fn lex_group(&mut self) -> Result<Token, ParseError> {
+        let third = self.input.chars().nth_back(2);
+
+        let look = match (self.peek_char(), self.peek_second_char(), third) {
+            (Some('?'), Some(c @ ('=' | '!')), _) => Some((2, true, c == '!')),
+            (Some('?'), Some('<'), Some(c @ ('=' | '!'))) => Some((3, false, c == '!')),
+            _ => None,
+        };
+
+        if let Some((length, ahead, negated)) = look {
+            for _ in 0..length { self.input.pop(); }
+            return Ok(Token::Look { ahead, negated })
+        }
+
+        if (self.peek_char(), self.peek_second_char()) == (Some('?'), Some('>')) {
+            self.input.pop();
+            self.input.pop();
+            return Ok(Token::Atomic)
+        }
+
+        let named = match (self.peek_char(), self.peek_second_char()) {
+            (Some('?'), Some('<')) => 2,
+            (Some('?'), Some('P')) if third == Some('<') => 3,
+            (Some('?'), _) => return self.lex_flags(),
+            _ => return Ok(Token::LParen),
+        };
+        for _ in 0..named { self.input.pop(); }
+
+        let mut name = String::new();
+        loop {
+            match self.input.pop() {
+                Some('>') => break,
+                Some(c) => name.push(c),
+                None => return Err(ParseError::InvalidGroupName(name)),
+            }
+        }
+
+        match is_group_name(&name) {
+            true => Ok(Token::NamedGroup(name)),
+            false => Err(ParseError::InvalidGroupName(name)),
+        }
+    }
+
+    // Lexes `?flags:` or `?flags)` after a '(', where flags are letters,
+    // optionally followed by '-' and the letters to clear.
+    fn lex_flags(&mut self) -> Result<Token, ParseError> {
+        let start = self.position() - 1;
+        self.input.pop(); //Consume '?'
+
+        let mut flags = Flags::default();
+        let mut negated = false;
+        let mut dangling = false;
+
+        loop {
+            let c = self.input.pop();
+            let span = Span { start, end: self.position() };
+
+            match c {
+                Some(':' | ')') if dangling => return Err(ParseError::InvalidFlags(span)),
+                Some(':') => return Ok(Token::NonCapturing(flags)),
+                Some(')') if flags == Flags::default() => return Err(ParseError::InvalidFlags(span)),
+                Some(')') => return Ok(Token::SetFlags(flags)),
+                Some('-') if negated => return Err(ParseError::InvalidFlags(span)),
+                Some('-') => {
+                    negated = true;
+                    dangling = true;
+                },
+                Some(c) => match flags.get_mut(c) {
+                    Some(Some(_)) => return Err(ParseError::InvalidFlags(span)),
+                    Some(flag) => {
+                        *flag = Some(!negated);
+                        dangling = false;
+                    },
+                    None => return Err(ParseError::UnknownFlag(c)),
+                },
+                None => return Err(ParseError::InvalidFlags(span)),
+            }
+        }
+    }
+
+    fn lex_class(&mut self) -> Result<Token, ParseError> {
+        self.lex_class_set().map(Token::Class)
+    }
+
+    // Lexes a bracket expression after the opening '['. Unions of items are
+    // combined left to right with '&&' (intersection), '--' (difference) and
+    // '~~' (symmetric difference), and a leading '^' negates the result.
+    fn lex_class_set(&mut self) -> Result<CharClass, ParseError> {
+        let negated = self.peek_char() == Some('^');
+        if negated { self.input.pop(); }
+
+        let mut class = self.lex_class_union(true)?;
+
+        loop {
+            let operator = match (self.peek_char(), self.peek_second_char()) {
+                (Some(']'), _) => {
+                    self.input.pop();
+                    break
+                },
+                (Some(c @ ('&' | '-' | '~')), Some(d)) if c == d => c,
+                _ => unreachable!("class union stops at ']' or an operator"),
+            };
+            self.input.pop();
+            self.input.pop();
+
+            let rhs = self.lex_class_union(false)?;
+
+            class = match operator {
+                '&' => class.intersect(&rhs),
+                '-' => class.difference(&rhs),
+                _ => class.symmetric_difference(&rhs),
+            };
+        }
+
+        match negated {
+            true => Ok(class.negate()),
+            false => Ok(class),
+        }
+    }
+
+    // Lexes class items up to the closing ']' or a set operator, neither of
+    // which is consumed. When `leading` is set, a ']' in first position is
+    // a literal, and so is any run of '-' there. Under `(?i)` the union is
+    // case folded, so that set operators combine folded operands.
+    fn lex_class_union(&mut self, leading: bool) -> Result<CharClass, ParseError> {
+        let mut class = CharClass::empty();
+        let mut first = leading;
+
+        while leading && self.peek_char() == Some('-') {
+            self.input.pop();
+            class.push('-', '-');
+            first = false;
+        }
+
+        loop {
+            match (self.peek_char(), self.peek_second_char()) {
+                (Some(']'), _) if !first => break,
+                (Some(c @ ('&' | '-' | '~')), Some(d)) if c == d => break,
+                _ => {},
+            }
+
+            let start = match self.input.pop() {
+                Some('\\') => match self.lex_escape()? {
+                    Token::Literal(c) => c,
+                    Token::Class(other) => {
+                        class.union(&other);
+                        first = false;
+                        continue
+                    },
+                    t => return Err(ParseError::UnexpectedToken(t)),
+                },
+                Some('[') => {
+                    let other = match self.peek_char() == Some(':') {
+                        true => self.lex_posix_class(),
+                        false => None,
+                    };
+                    class.union(&other.unwrap_or_else(|| self.lex_class_set())?);
+                    first = false;
+                    continue
+                },
+                Some(c) => c,
+                None => return Err(ParseError::UnclosedClass),
+            };
+            first = false;
+
+            let is_range = self.peek_char() == Some('-') &&
+                           !matches!(self.peek_second_char(), Some(']') | Some('-') | None);
+
+            if is_range {
+                self.input.pop(); //Consume '-'
+                let end = self.input.pop().ok_or(ParseError::UnclosedClass)?;
+                let end = self.lex_class_literal(end)?;
+                if start > end { return Err(ParseError::InvalidClassRange(start, end)) }
+                class.push(start, end);
+            } else {
+                class.push(start, start);
+            }
+        }
+
+        if self.config.case_insensitive { class = self.case_fold(class); }
+
+        Ok(class)
+    }
+
+    // Lexes `:name:]` or `:^name:]` after a '[' inside a bracket expression.
+    // Returns None, consuming nothing, when the text doesn't have that shape.
+    fn lex_posix_class(&mut self) -> Option<Result<CharClass, ParseError>> {
+        let rest = self.input.chars().rev().skip(1); //Skip ':'
+        let body: String = rest.take_while(|&c| c != ':' && c != ']').collect();
+        let closing = self.input.chars().rev().skip(1 + body.chars().count()).take(2);
+
+        if !closing.eq([':', ']']) { return None }
+
+        for _ in 0..body.chars().count() + 3 { self.input.pop(); }
+
+        let (negated, name) = match body.strip_prefix('^') {
+            Some(name) => (true, name),
+            None => (false, body.as_str()),
+        };
+
+        match (CharClass::posix(name), negated) {
+            (Some(class), true) => Some(Ok(class.negate())),
+            (Some(class), false) => Some(Ok(class)),
+            (None, _) => Some(Err(ParseError::UnknownPosixClass(name.to_string()))),
+        }
+    }
+
+    fn lex_class_literal(&mut self, c: char) -> Result<char, ParseError> {
+        match c {
+            '\\' => match self.lex_escape()? {
+                Token::Literal(c) => Ok(c),
+                t => Err(ParseError::UnexpectedToken(t)),
+            },
+            c => Ok(c),
+        }
+    }
+
+    // Lexes the remainder of an escape sequence after the backslash.
+    fn lex_escape(&mut self) -> Result<Token, ParseError> {
+        let start = self.position() - 1;
+
+        match self.input.pop() {
+            Some('n') => Ok(Token::Literal('\n')),
+            Some('t') => Ok(Token::Literal('\t')),
+            Some('r') => Ok(Token::Literal('\r')),
+            Some('0') => Ok(Token::Literal('\0')),
+            Some('A') => Ok(Token::StartText),
+            Some('z') => Ok(Token::EndText),
+            Some('b') => Ok(Token::WordBoundary),
+            Some('B') => Ok(Token::NotWordBoundary),
+            Some('<') => Ok(Token::WordStart),
+            Some('>') => Ok(Token::WordEnd),
+            Some(c @ ('d' | 'D' | 'w' | 'W' | 's' | 'S')) => Ok(Token::Class(CharClass::perl(c, self.config.unicode))),
+            Some(c @ ('p' | 'P')) => self.lex_unicode_class(c == 'P'),
+            Some(c @ ('x' | 'u')) => self.lex_hex_escape(c, start).map(Token::Literal),
+            Some(c @ '1'..='9') => self.lex_backref(c),
+            Some('k') => self.lex_named_backref(),
+            Some(c) if is_escapable(c) => Ok(Token::Literal(c)),
+            Some(c) => Err(ParseError::InvalidEscape(c)),
+            None => Err(ParseError::TrailingBackslash),
+        }
+    }
+
+    // Lexes the rest of the group number of `\N` after its first digit.
+    fn lex_backref(&mut self, first: char) -> Result<Token, ParseError> {
+        let mut digits = first.to_string();
+
+        while let Some(c) = self.peek_char().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.input.pop();
+        }
+
+        digits.parse().map(Token::Backref).map_err(|_| ParseError::InvalidEscape(first))
+    }
+
+    // Lexes `<name>` after `\k`.
+    fn lex_named_backref(&mut self) -> Result<Token, ParseError> {
+        if self.input.pop() != Some('<') { return Err(ParseError::InvalidEscape('k')) }
+
+        let mut name = String::new();
+        loop {
+            match self.input.pop() {
+                Some('>') => break,
+                Some(c) => name.push(c),
+                None => return Err(ParseError::InvalidGroupName(name)),
+            }
+        }
+
+        match is_group_name(&name) {
+            true => Ok(Token::NamedBackref(name)),
+            false => Err(ParseError::InvalidGroupName(name)),
+        }
+    }
+
+    // Lexes the property name of `\pL` or `\p{...}` after the 'p'.
+    fn lex_unicode_class(&mut self, negated: bool) -> Result<Token, ParseError> {
+        let name = match self.input.pop() {
+            Some('{') => {
+                let mut name = String::new();
+                loop {
+                    match self.input.pop() {
+                        Some('}') => break name,
+                        Some(c) => name.push(c),
+                        None => return Err(ParseError::UnclosedClass),
+                    }
+                }
+            },
+            Some(c) => c.to_string(),
+            None => return Err(ParseError::MissingPropertyName),
+        };
+
+        if name.is_empty() { return Err(ParseError::MissingPropertyName) }
+
+        let mut class = unicode::property_class(&name).ok_or(ParseError::UnknownUnicodeProperty(name))?;
+        if self.config.case_insensitive { class = self.case_fold(class); }
+
+        match negated {
+            true => Ok(Token::Class(class.negate())),
+            false => Ok(Token::Class(class)),
+        }
+    }
+
+    // Lexes the digits of `\xHH`, `\uHHHH`, `\x{H...}` or `\u{H...}`. `start`
+    // is the offset of the backslash, so errors can point at the whole escape.
+    fn lex_hex_escape(&mut self, kind: char, start: usize) -> Result<char, ParseError> {
+        let braced = self.peek_char() == Some('{');
+        if braced { self.input.pop(); }
+
+        let mut digits = String::new();
+
+        while let Some(c) = self.peek_char().filter(char::is_ascii_hexdigit) {
+            if !braced && digits.len() == if kind == 'x' { 2 } else { 4 } { break }
+            digits.push(c);
+            self.input.pop();
+        }
+
+        let well_formed = match braced {
+            true => !digits.is_empty() && self.input.pop() == Some('}'),
+            false => digits.len() == if kind == 'x' { 2 } else { 4 },
+        };
+
+        let span = Span { start, end: self.position() };
+
+        if !well_formed { return Err(ParseError::InvalidHexEscape(span)) }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(ParseError::InvalidCodePoint(span))
+    }
+
+    fn lex_number(&mut self) -> Option<Result<usize, ParseError>> {
+        let mut digits = String::new();
+
+        while let Some(c) = self.peek_char().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.input.pop();
+        }
+
+        if digits.is_empty() { return None }
+
+        Some(digits.parse().map_err(|_| ParseError::InvalidRepetition))
+    }
+
+    // Lexes the remainder of `{n}`, `{n,}` or `{n,m}` after the opening brace.
+    fn lex_repetition(&mut self) -> Result<Token, ParseError> {
+        self.skip_ignored();
+        let min = self.lex_number().ok_or(ParseError::InvalidRepetition)??;
+        self.skip_ignored();
+
+        let max = match self.input.pop() {
+            Some('}') => return Ok(Token::Repeat { min, max: Some(min) }),
+            Some(',') => {
+                self.skip_ignored();
+                let max = self.lex_number().transpose()?;
+                self.skip_ignored();
+                max
+            },
+            _ => return Err(ParseError::InvalidRepetition),
+        };
+
+        if self.input.pop() != Some('}') { return Err(ParseError::InvalidRepetition) }
+
+        match max {
+            Some(max) if max < min => Err(ParseError::InvalidRepetition),
+            _ => Ok(Token::Repeat { min, max }),
         }
     }
 
@@ -79,4 +538,16 @@ impl Lexer{
 
         Some(Ok(collected))
     }
-}
\ No newline at end of file
+}
+
+// Any ASCII character other than a letter or digit may be escaped to match itself.
+fn is_escapable(c: char) -> bool {
+    c.is_ascii() && !c.is_ascii_alphanumeric()
+}
+
+// Group names start with a letter or '_' and continue with letters, digits or '_'.
+fn is_group_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_') &&
+        chars.all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -1,35 +1,114 @@
 pub mod errors;
+mod backtrack;
+pub mod class;
+mod unicode;
+mod unicode_tables;
 pub mod token;
 pub mod lexer;
+mod look;
+mod posix;
 pub mod nfa;
 pub mod parser;
 pub mod matcher;
+pub mod builder;
+pub mod matches;
+
+use std::{collections::HashMap, sync::Arc};
 
 pub use crate::{
     lexer::Lexer,
-    errors::ParseError,
+    errors::{ParseError, MatchError, Span},
     parser::Parser,
     matcher::Matcher,
+    builder::{RRegexBuilder, MatchKind},
+    matches::{Match, Matches, Captures},
 };
 
+/// A compiled pattern.
+///
+/// Patterns with backreferences run on a backtracking engine with a step
+/// budget (see `RRegexBuilder::backtrack_limit`). The `try_*` methods report
+/// an exhausted budget as a `MatchError`; the other search methods panic on
+/// it. Patterns without backreferences never fail.
 pub struct RRegex { 
-    matcher: Matcher
+    matcher: Matcher,
+    group_names: Vec<Option<String>>,
+    name_to_index: Arc<HashMap<String, usize>>,
 }
 
 impl RRegex {
     pub fn new(regex: String) -> Result<Self, ParseError> {
-        let mut lexer = Lexer::new(regex);
-        let mut parser = Parser::new(&mut lexer);
-        let nfa = parser.parse()?;
-        let matcher = Matcher::new(nfa);
+        RRegexBuilder::new(&regex).build()
+    }
+
+    pub(crate) fn from_parts(matcher: Matcher, group_names: Vec<Option<String>>) -> Self {
+        let name_to_index = group_names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| Some((name.clone()?, index)))
+            .collect();
+
+        RRegex { matcher, group_names, name_to_index: Arc::new(name_to_index) }
+    }
+
+    /// Whether the pattern matches anywhere in `input`.
+    pub fn is_match(&self, input: &str) -> bool {
+        self.try_is_match(input).unwrap_or_else(|e| panic!("{e}"))
+    }
 
-        Ok(RRegex { matcher })
+    pub fn try_is_match(&self, input: &str) -> Result<bool, MatchError> {
+        self.matcher.try_is_match(input)
     }
 
-    pub fn matches(&self, input: &str) -> bool {
-        self.matcher.set_simulation(input)
+    /// Finds the leftmost match in `input`. When several matches start at the
+    /// same position, the one preferred by the pattern wins: earlier
+    /// alternatives over later ones, and greedy repetitions take as much as
+    /// they can, as in Perl and the `regex` crate.
+    pub fn find<'h>(&self, input: &'h str) -> Option<Match<'h>> {
+        self.try_find(input).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_find<'h>(&self, input: &'h str) -> Result<Option<Match<'h>>, MatchError> {
+        let found = self.matcher.try_find_at(input, 0)?;
+
+        Ok(found.map(|(start, end)| Match::new(input, start, end)))
+    }
+
+    /// Iterates over the successive non-overlapping matches in `input`, each
+    /// found as by `find`. An empty match is not reported directly after the
+    /// end of the previous match. Panics as `find` does.
+    pub fn find_iter<'r, 'h>(&'r self, input: &'h str) -> Matches<'r, 'h> {
+        Matches::new(&self.matcher, input)
+    }
+
+    /// Finds the leftmost match in `input`, as `find` does, along with the span
+    /// of every capture group. A group inside a repetition reports its last
+    /// iteration, and a group inside a look-around is never set.
+    pub fn captures<'h>(&self, input: &'h str) -> Option<Captures<'h>> {
+        self.try_captures(input).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_captures<'h>(&self, input: &'h str) -> Result<Option<Captures<'h>>, MatchError> {
+        let slots = self.matcher.try_captures_at(input, 0, 2 * self.matcher.group_count())?;
+
+        Ok(slots.map(|slots| Captures::new(input, slots, Arc::clone(&self.name_to_index))))
+    }
+
+    /// The names of the capture groups in index order, starting with group 0.
+    /// Unnamed groups, including group 0, are yielded as `None`.
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.group_names.iter().map(Option::as_deref)
+    }
+
+    /// Whether the pattern matches the whole of `input`.
+    pub fn is_full_match(&self, input: &str) -> bool {
+        self.try_is_full_match(input).unwrap_or_else(|e| panic!("{e}"))
         // self.matcher.copy_simulation(input)
     }
+
+    pub fn try_is_full_match(&self, input: &str) -> Result<bool, MatchError> {
+        self.matcher.try_is_full_match(input)
+    }
 }
 
 #[cfg(test)]
@@ -42,10 +121,10 @@ mod tests {
         let expected = regex::Regex::new(&format!("^({})$", &s1)[..]).unwrap().is_match(&s2);
 
         let rregex = RRegex::new(s1.clone())
-            .expect(&format!("Failed to create regex ('{s1}'). Regex parsed {expected}.")[..]);
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected}."));
 
         assert_eq!(
-            rregex.matches(&s2),
+            rregex.is_full_match(&s2),
             expected,
             "Test failed for regex: '{}', input: '{}'",
             s1,
@@ -53,6 +132,66 @@ mod tests {
         );
     }
 
+    fn test_matches_multi_line(s1: &str, s2: &str) {
+        let expected = regex::RegexBuilder::new(&format!(r"\A({s1})\z"))
+            .multi_line(true)
+            .build()
+            .unwrap()
+            .is_match(s2);
+
+        let rregex = RRegexBuilder::new(s1)
+            .multi_line(true)
+            .build()
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected}."));
+
+        assert_eq!(rregex.is_full_match(s2), expected, "Multi-line test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    fn test_is_match(s1: &str, s2: &str) {
+        let expected = regex::Regex::new(s1).unwrap().is_match(s2);
+
+        let rregex = RRegex::new(s1.to_string())
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected}."));
+
+        assert_eq!(rregex.is_match(s2), expected, "Search test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    fn test_find(s1: &str, s2: &str) {
+        let expected = regex::Regex::new(s1).unwrap().find(s2).map(|m| (m.start(), m.end()));
+
+        let rregex = RRegex::new(s1.to_string())
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected:?}."));
+
+        let found = rregex.find(s2);
+        assert_eq!(found.map(|m| (m.start(), m.end())), expected, "Find test failed for regex: '{s1}', input: '{s2}'");
+
+        if let Some(m) = found {
+            assert_eq!(m.as_str(), &s2[m.start()..m.end()]);
+        }
+    }
+
+    fn test_find_iter(s1: &str, s2: &str) {
+        let expected: Vec<_> = regex::Regex::new(s1).unwrap().find_iter(s2).map(|m| (m.start(), m.end())).collect();
+
+        let rregex = RRegex::new(s1.to_string())
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected:?}."));
+
+        let found: Vec<_> = rregex.find_iter(s2).map(|m| (m.start(), m.end())).collect();
+        assert_eq!(found, expected, "Find iter test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    fn test_captures(s1: &str, s2: &str) {
+        let expected: Option<Vec<_>> = regex::Regex::new(s1).unwrap().captures(s2)
+            .map(|caps| caps.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect());
+
+        let rregex = RRegex::new(s1.to_string())
+            .unwrap_or_else(|_| panic!("Failed to create regex ('{s1}'). Regex parsed {expected:?}."));
+
+        let found: Option<Vec<_>> = rregex.captures(s2)
+            .map(|caps| caps.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect());
+        assert_eq!(found, expected, "Captures test failed for regex: '{s1}', input: '{s2}'");
+    }
+
     #[test]
     fn test_regex_literals() {
         // Positive test cases
@@ -171,4 +310,1321 @@ mod tests {
         test_matches("(a*)*", "a"); // Nested Kleene star matches single 'a'
         test_matches("(a*)*", "aaaa"); // Nested Kleene star matches multiple 'a's
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_kleene_plus() {
+        // Positive test cases
+        test_matches("a+", "a"); // Single 'a' matches "a+"
+        test_matches("a+", "aaa"); // Multiple 'a's match "a+"
+        test_matches("(ab)+", "ab"); // Single "ab" matches "(ab)+"
+        test_matches("(ab)+", "ababab"); // Multiple "ab"s match "(ab)+"
+        test_matches("a+b", "ab"); // One 'a' followed by 'b' matches "a+b"
+        test_matches("a+b", "aaab"); // Multiple 'a's followed by 'b' match "a+b"
+
+        // Negative test cases
+        test_matches("a+", ""); // Empty string does not match "a+" (at least one 'a')
+        test_matches("a+", "b"); // "b" does not match "a+"
+        test_matches("a+b", "b"); // Missing 'a' before 'b'
+        test_matches("(ab)+", "aba"); // Trailing partial repetition
+
+        // Edge cases
+        test_matches("(a|b)+", "abba"); // Union inside plus
+        test_matches("(a*)+", ""); // Nullable body lets plus match the empty string
+        test_matches("(a+)*", "aaa"); // Plus nested inside star
+        test_matches("(a|)+", ""); // Empty alternative inside plus
+    }
+
+    #[test]
+    fn test_question() {
+        // Positive test cases
+        test_matches("a?", ""); // Zero occurrences match "a?"
+        test_matches("a?", "a"); // One occurrence matches "a?"
+        test_matches("ab?c", "ac"); // Optional 'b' absent
+        test_matches("ab?c", "abc"); // Optional 'b' present
+        test_matches("(ab)?c", "abc"); // Optional group present
+
+        // Negative test cases
+        test_matches("a?", "aa"); // At most one 'a'
+        test_matches("ab?c", "abbc"); // At most one 'b'
+        test_matches("(ab)?c", "ac"); // Optional group is all or nothing
+
+        // Edge cases
+        test_matches("a?b?", ""); // Both optional parts absent
+        test_matches("a?b?", "b"); // Only the second optional part present
+        test_matches("(a?)*", "aa"); // Optional nested inside star
+        test_matches("(a|b)?", "b"); // Union inside optional
+    }
+
+    #[test]
+    fn test_counted_repetition() {
+        // Positive test cases
+        test_matches("a{3}", "aaa"); // Exact count
+        test_matches("a{2,}", "aa"); // Lower bound only, minimum
+        test_matches("a{2,}", "aaaaa"); // Lower bound only, more than minimum
+        test_matches("a{2,4}", "aa"); // Range, minimum
+        test_matches("a{2,4}", "aaaa"); // Range, maximum
+        test_matches("(ab){2}", "abab"); // Repetition of a group
+        test_matches("(a|b){3}", "bab"); // Repetition of a union
+
+        // Negative test cases
+        test_matches("a{3}", "aa"); // Too few
+        test_matches("a{3}", "aaaa"); // Too many
+        test_matches("a{2,}", "a"); // Below the lower bound
+        test_matches("a{2,4}", "aaaaa"); // Above the upper bound
+
+        // Edge cases
+        test_matches("a{0}", ""); // Zero repetitions match the empty string
+        test_matches("a{0}", "a"); // Zero repetitions reject any input
+        test_matches("a{0,}", "aaa"); // Equivalent to a star
+        test_matches("a{1,1}", "a"); // Degenerate range
+        test_matches("a{0,2}b", "b"); // Optional prefix absent
+        test_matches("a{2}{3}", "aaaaaa"); // Stacked repetitions multiply
+        test_matches("a{2}{3}", "aaaaa"); // Stacked repetitions need every copy
+        test_matches("a{2}*", "aaaa"); // Star over a counted repetition without a group
+        test_matches("a{2}*", "aaa"); // Only whole copies of the inner repetition
+        test_matches("a**", "aaa"); // Stacked stars
+        test_matches("a?{2}", "a"); // Counted repetition of an optional
+        test_matches("(a{2})*", "aaaa"); // Star over a counted repetition
+    }
+
+    #[test]
+    fn test_invalid_repetition() {
+        assert!(matches!(RRegex::new("a{".to_string()), Err(ParseError::InvalidRepetition)));
+        assert!(matches!(RRegex::new("a{2".to_string()), Err(ParseError::InvalidRepetition)));
+        assert!(matches!(RRegex::new("a{x}".to_string()), Err(ParseError::InvalidRepetition)));
+        assert!(matches!(RRegex::new("a{,3}".to_string()), Err(ParseError::InvalidRepetition)));
+        assert!(matches!(RRegex::new("a{3,2}".to_string()), Err(ParseError::InvalidRepetition)));
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let small = RRegexBuilder::new("a{1000}").size_limit(100).build();
+        assert!(matches!(small, Err(ParseError::SizeLimitExceeded(100))));
+
+        let nested = RRegexBuilder::new("((a{10}){10}){10}").size_limit(1000).build();
+        assert!(matches!(nested, Err(ParseError::SizeLimitExceeded(1000))));
+
+        // Counts at the limit of usize are refused before anything is expanded
+        let max = usize::MAX;
+        for pattern in [format!("a{{{max}}}"), format!("a{{{max},}}"), format!("a{{0,{max}}}"), format!("(?:){{{max}}}")] {
+            assert!(matches!(RRegex::new(pattern.clone()), Err(ParseError::SizeLimitExceeded(_))), "{pattern}");
+        }
+
+        let within = RRegexBuilder::new("a{10}").size_limit(100).build().unwrap();
+        assert!(within.is_full_match("aaaaaaaaaa"));
+    }
+
+    #[test]
+    fn test_bracket_classes() {
+        // Positive test cases
+        test_matches("[abc]", "b"); // Member of a set
+        test_matches("[a-z]", "q"); // Member of a range
+        test_matches("[a-cx-z]", "y"); // Member of the second range
+        test_matches("[a-z]+", "hello"); // Repeated class
+        test_matches("[^abc]", "d"); // Not a member of a negated set
+        test_matches("[α-ω]", "λ"); // Non-ASCII range
+
+        // Negative test cases
+        test_matches("[abc]", "d"); // Not a member of a set
+        test_matches("[a-z]", "A"); // Outside the range
+        test_matches("[^abc]", "a"); // Member of a negated set
+        test_matches("[^a-z]", "m"); // Inside a negated range
+        test_matches("[abc]", "ab"); // A class consumes exactly one character
+
+        // Edge cases
+        test_matches("[]a]", "]"); // Leading ']' is a literal
+        test_matches("[^]a]", "]"); // Leading ']' after '^' is a literal
+        test_matches("[a-]", "-"); // Trailing '-' is a literal
+        test_matches("[-a]", "-"); // Leading '-' is a literal
+        test_matches("[-a]", "a"); // And the next item is unaffected
+        test_matches("[--a]", "-"); // A run of leading '-' is literal, not a difference
+        test_matches("[--a]", "E"); // Nor a range from '-' to 'a'
+        test_matches("[^--a]", "-"); // Leading '-' after '^' is a literal
+        test_matches("[]--a]", "]"); // After a leading ']', '--' is a difference
+        test_matches("[a-ca-c]", "b"); // Duplicate ranges are merged
+        test_matches("[a-cb-e]", "e"); // Overlapping ranges are merged
+        test_matches("[^a]", "\n"); // Negated classes match newlines
+        test_matches("[^a]", "😀"); // Negated classes match astral characters
+        test_matches("[*|()]", "*"); // Metacharacters are literal in a class
+    }
+
+    #[test]
+    fn test_invalid_classes() {
+        assert!(matches!(RRegex::new("[abc".to_string()), Err(ParseError::UnclosedClass)));
+        assert!(matches!(RRegex::new("[]".to_string()), Err(ParseError::UnclosedClass)));
+        assert!(matches!(RRegex::new("[z-a]".to_string()), Err(ParseError::InvalidClassRange('z', 'a'))));
+    }
+
+    #[test]
+    fn test_class_negation() {
+        let class = class::CharClass::new(vec![('a', 'z'), ('\u{D7FF}', '\u{E000}')]);
+        let negated = class.negate();
+
+        assert!(!negated.contains('m'));
+        assert!(negated.contains('\0'));
+        assert!(negated.contains(char::MAX));
+        assert_eq!(negated.negate(), class);
+        assert_eq!(class.ranges(), &[('a', 'z'), ('\u{D7FF}', '\u{E000}')]);
+    }
+
+    #[test]
+    fn test_dot() {
+        // Positive test cases
+        test_matches(".", "a"); // Any single character
+        test_matches("a.c", "abc"); // Dot between literals
+        test_matches(".*", "anything"); // Dot star
+        test_matches(".", "é"); // Non-ASCII character
+        test_matches(".", "😀"); // Astral character
+
+        // Negative test cases
+        test_matches(".", ""); // Dot needs one character
+        test_matches(".", "ab"); // Dot consumes exactly one character
+        test_matches(".", "\n"); // Dot does not match a newline by default
+        test_matches("a.*c", "a\nc"); // Newline stops a dot star
+
+        // Edge cases
+        test_matches("[.]", "."); // Dot is literal inside a class
+        test_matches("[.]", "a"); // Literal dot does not match other characters
+    }
+
+    #[test]
+    fn test_dot_matches_new_line() {
+        let rregex = RRegexBuilder::new("a.c").dot_matches_new_line(true).build().unwrap();
+        assert!(rregex.is_full_match("a\nc"));
+        assert!(rregex.is_full_match("abc"));
+        assert!(rregex.matcher.copy_simulation("a\nc"));
+
+        let rregex = RRegexBuilder::new("a.c").dot_matches_new_line(false).build().unwrap();
+        assert!(!rregex.is_full_match("a\nc"));
+        assert!(!rregex.matcher.copy_simulation("a\nc"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        // Positive test cases
+        test_matches(r"a\*", "a*"); // Escaped star is a literal
+        test_matches(r"\(a\)", "(a)"); // Escaped parentheses
+        test_matches(r"a\|b", "a|b"); // Escaped union
+        test_matches(r"\\", "\\"); // Escaped backslash
+        test_matches(r"\.", "."); // Escaped dot
+        test_matches(r"\[a\]", "[a]"); // Escaped brackets
+        test_matches(r"a\{2\}", "a{2}"); // Escaped braces
+        test_matches(r"a\nb", "a\nb"); // Newline escape
+        test_matches(r"a\tb", "a\tb"); // Tab escape
+        test_matches(r"\r\n", "\r\n"); // Carriage return escape
+        test_matches(r"[\]]", "]"); // Escaped bracket inside a class
+        test_matches(r"[a\-z]", "-"); // Escaped dash inside a class is a literal
+        test_matches(r"[\t-\r]", "\n"); // Escapes as range endpoints
+
+        // Negative test cases
+        test_matches(r"a\*", "aa"); // Escaped star does not repeat
+        test_matches(r"\.", "a"); // Escaped dot only matches a dot
+        test_matches(r"[a\-z]", "m"); // Escaped dash does not form a range
+
+        // Edge cases
+        test_matches("1+", "111"); // Digits are literals
+        test_matches("a b", "a b"); // Spaces are literals
+        test_matches("a-b", "a-b"); // Dashes are literals outside a class
+        test_matches("a]", "a]"); // Unbalanced closing bracket is a literal
+        test_matches("a}", "a}"); // Unbalanced closing brace is a literal
+        test_matches("é|ü", "ü"); // Non-ASCII literals
+        test_matches("a\nb", "a\nb"); // Raw newline in the pattern
+    }
+
+    #[test]
+    fn test_nul_escape() {
+        let rregex = RRegex::new(r"a\0b".to_string()).unwrap();
+        assert!(rregex.is_full_match("a\0b"));
+        assert!(!rregex.is_full_match("a0b"));
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        assert!(matches!(RRegex::new(r"a\".to_string()), Err(ParseError::TrailingBackslash)));
+        assert!(matches!(RRegex::new(r"[a\".to_string()), Err(ParseError::TrailingBackslash)));
+        assert!(matches!(RRegex::new(r"\q".to_string()), Err(ParseError::InvalidEscape('q'))));
+        assert!(matches!(RRegex::new(r"[\<]".to_string()), Err(ParseError::UnexpectedToken(token::Token::WordStart))));
+    }
+
+    #[test]
+    fn test_perl_classes() {
+        // Positive test cases
+        test_matches(r"\d+", "2024"); // ASCII digits
+        test_matches(r"\w+", "snake_case9"); // Word characters
+        test_matches(r"a\sb", "a b"); // Space
+        test_matches(r"a\sb", "a\tb"); // Tab
+        test_matches(r"\D", "x"); // Non-digit
+        test_matches(r"\W", "-"); // Non-word character
+        test_matches(r"\S", "x"); // Non-space
+
+        // Negative test cases
+        test_matches(r"\d", "x"); // Letter is not a digit
+        test_matches(r"\w", "-"); // Dash is not a word character
+        test_matches(r"\s", "x"); // Letter is not a space
+        test_matches(r"\D", "7"); // Digit is not a non-digit
+        test_matches(r"\W", "_"); // Underscore is a word character
+        test_matches(r"\S", "\n"); // Newline is a space
+
+        // Edge cases
+        test_matches(r"\d", "٣"); // Arabic-Indic digit is a Unicode digit
+        test_matches(r"\w+", "naïve"); // Accented letters are word characters
+        test_matches(r"\w", "\u{300}"); // Combining marks are word characters
+        test_matches(r"\s", "\u{2003}"); // Em space is a Unicode space
+        test_matches(r"\s", "\u{85}"); // Next line is a Unicode space
+        test_matches(r"[\d_]+", "1_000"); // Shorthand inside a class
+        test_matches(r"[\D]", "a"); // Negated shorthand inside a class
+        test_matches(r"[^\d]", "5"); // Shorthand inside a negated class
+        test_matches(r"[\s\d]", " "); // Several shorthands inside a class
+        test_matches(r"[a\w-]", "-"); // Dash after a shorthand is a literal
+    }
+
+    #[test]
+    fn test_ascii_perl_classes() {
+        let digit = RRegexBuilder::new(r"\d").unicode(false).build().unwrap();
+        assert!(digit.is_full_match("7"));
+        assert!(!digit.is_full_match("٣"));
+
+        let word = RRegexBuilder::new(r"\w+").unicode(false).build().unwrap();
+        assert!(word.is_full_match("snake_case9"));
+        assert!(!word.is_full_match("naïve"));
+
+        let space = RRegexBuilder::new(r"\s").unicode(false).build().unwrap();
+        assert!(space.is_full_match("\x0B"));
+        assert!(!space.is_full_match("\u{2003}"));
+
+        let not_word = RRegexBuilder::new(r"[\W]").unicode(false).build().unwrap();
+        assert!(not_word.is_full_match("ï"));
+        assert!(!not_word.is_full_match("_"));
+    }
+
+    #[test]
+    fn test_hex_escapes() {
+        // Positive test cases
+        test_matches(r"\x41", "A"); // Two-digit hex escape
+        test_matches(r"\x{41}", "A"); // Braced hex escape
+        test_matches(r"\u{3b1}", "α"); // Braced Unicode escape
+        test_matches(r"\u03b1", "α"); // Four-digit Unicode escape
+        test_matches(r"\x{1F600}", "😀"); // Astral code point
+        test_matches(r"[\x{3b1}-\x{3c9}]+", "λόγος"); // Escapes as class endpoints
+
+        // Negative test cases
+        test_matches(r"\x41", "a"); // Hex escapes are exact
+        test_matches(r"\x411", "A"); // Only two digits belong to the escape
+
+        // Edge cases
+        test_matches(r"\x411", "A1"); // Trailing digit is a literal
+        test_matches(r"\x{0000041}", "A"); // Leading zeros are allowed
+        test_matches(r"\x2a+", "***"); // Escaped metacharacter stays a literal
+    }
+
+    #[test]
+    fn test_invalid_hex_escapes() {
+        let surrogate = RRegex::new(r"a\x{D800}".to_string());
+        assert!(matches!(surrogate, Err(ParseError::InvalidCodePoint(Span { start: 1, end: 9 }))));
+
+        let too_large = RRegex::new(r"\u{110000}b".to_string());
+        assert!(matches!(too_large, Err(ParseError::InvalidCodePoint(Span { start: 0, end: 10 }))));
+
+        let overflow = RRegex::new(r"\x{FFFFFFFFF}".to_string());
+        assert!(matches!(overflow, Err(ParseError::InvalidCodePoint(_))));
+
+        let in_class = RRegex::new(r"[é\x{DFFF}]".to_string());
+        assert!(matches!(in_class, Err(ParseError::InvalidCodePoint(Span { start: 3, end: 11 }))));
+
+        assert!(matches!(RRegex::new(r"\xZZ".to_string()), Err(ParseError::InvalidHexEscape(_))));
+        assert!(matches!(RRegex::new(r"\x4".to_string()), Err(ParseError::InvalidHexEscape(_))));
+        assert!(matches!(RRegex::new(r"\x{}".to_string()), Err(ParseError::InvalidHexEscape(_))));
+        assert!(matches!(RRegex::new(r"\x{41".to_string()), Err(ParseError::InvalidHexEscape(_))));
+        assert!(matches!(RRegex::new(r"\u{4g}".to_string()), Err(ParseError::InvalidHexEscape(_))));
+    }
+
+    #[test]
+    fn test_unicode_properties() {
+        // Positive test cases
+        test_matches(r"\p{L}+", "Straße"); // Letters
+        test_matches(r"\pL", "ж"); // Single-letter category name
+        test_matches(r"\p{Lu}", "Ä"); // Uppercase letter
+        test_matches(r"\p{Uppercase_Letter}", "Ä"); // Long category name
+        test_matches(r"\p{Greek}+", "λόγος"); // Script
+        test_matches(r"\p{sc=Cyrillic}+", "привет"); // Explicit script property
+        test_matches(r"\p{gc=Nd}", "٣"); // Explicit general category
+        test_matches(r"\p{Alphabetic}", "ª"); // Binary property
+        test_matches(r"\p{White_Space}", "\u{3000}"); // Binary property with an underscore
+        test_matches(r"\P{Greek}", "a"); // Negated script
+
+        // Negative test cases
+        test_matches(r"\p{L}", "1"); // Digit is not a letter
+        test_matches(r"\p{Lu}", "a"); // Lowercase is not uppercase
+        test_matches(r"\p{Greek}", "a"); // Latin is not Greek
+        test_matches(r"\P{Greek}", "λ"); // Greek is not non-Greek
+        test_matches(r"\p{Han}", "あ"); // Hiragana is not Han
+
+        // Edge cases
+        test_matches(r"\p{greek}", "λ"); // Names are case insensitive
+        test_matches(r"\p{ Script = Greek }", "λ"); // Spaces are ignored
+        test_matches(r"\p{uppercase-letter}", "Q"); // Dashes are ignored
+        test_matches(r"\p{Grek}", "λ"); // Short script name
+        test_matches(r"[\p{Greek}\d]+", "λ1"); // Property inside a class
+        test_matches(r"[^\p{L}]", "-"); // Property inside a negated class
+        test_matches(r"[\P{L}]", "a"); // Negated property inside a class
+        test_matches(r"\p{Any}", "\n"); // Any matches everything
+        test_matches(r"\p{ASCII}", "é"); // ASCII excludes non-ASCII
+        test_matches(r"\p{Emoji}", "😀"); // Emoji property
+        test_matches(r"\p{Cn}", "\u{378}"); // Unassigned code point
+        test_matches(r"\p{Assigned}", "\u{378}"); // Assigned excludes unassigned code points
+    }
+
+    #[test]
+    fn test_unknown_unicode_properties() {
+        let unknown = RRegex::new(r"\p{Klingon}".to_string());
+        assert!(matches!(unknown, Err(ParseError::UnknownUnicodeProperty(name)) if name == "Klingon"));
+
+        let bad_value = RRegex::new(r"\p{gc=Greek}".to_string());
+        assert!(matches!(bad_value, Err(ParseError::UnknownUnicodeProperty(_))));
+
+        assert!(matches!(RRegex::new(r"\p{Greek".to_string()), Err(ParseError::UnclosedClass)));
+        assert!(matches!(RRegex::new(r"\p".to_string()), Err(ParseError::MissingPropertyName)));
+        assert!(matches!(RRegex::new(r"[\P".to_string()), Err(ParseError::MissingPropertyName)));
+        assert!(matches!(RRegex::new(r"\p{}".to_string()), Err(ParseError::MissingPropertyName)));
+    }
+
+    #[test]
+    fn test_posix_classes() {
+        // Positive test cases
+        test_matches("[[:alpha:]]+", "abcXYZ"); // Letters
+        test_matches("[[:digit:]]{4}", "2024"); // Digits
+        test_matches("[[:space:]]", "\t"); // Whitespace
+        test_matches("[[:alnum:]]", "7"); // Letters and digits
+        test_matches("[[:upper:]][[:lower:]]+", "Hello"); // Case classes
+        test_matches("[[:punct:]]", "!"); // Punctuation
+        test_matches("[[:xdigit:]]+", "dEaD42"); // Hex digits
+        test_matches("[[:blank:]]", " "); // Blank
+        test_matches("[[:cntrl:]]", "\x07"); // Control character
+        test_matches("[[:graph:]]", "~"); // Visible character
+        test_matches("[[:print:]]", " "); // Printable character
+
+        // Negative test cases
+        test_matches("[[:alpha:]]", "1"); // Digit is not a letter
+        test_matches("[[:digit:]]", "٣"); // POSIX classes are ASCII only
+        test_matches("[[:space:]]", "x"); // Letter is not whitespace
+        test_matches("[[:graph:]]", " "); // Space is not visible
+        test_matches("[[:blank:]]", "\n"); // Newline is not blank
+
+        // Edge cases
+        test_matches("[[:^digit:]]", "a"); // Negated POSIX class
+        test_matches("[[:^digit:]]", "5"); // Negated POSIX class rejects its members
+        test_matches("[[:digit:][:upper:]]+", "A1"); // Two POSIX classes in one bracket
+        test_matches("[_[:alpha:]-]+", "a_b-c"); // POSIX class mixed with literals
+        test_matches("[^[:alpha:]]", "1"); // POSIX class inside a negated bracket
+    }
+
+    #[test]
+    fn test_unknown_posix_class() {
+        let unknown = RRegex::new("[[:klingon:]]".to_string());
+        assert!(matches!(unknown, Err(ParseError::UnknownPosixClass(name)) if name == "klingon"));
+
+        let negated = RRegex::new("[[:^Alpha:]]".to_string());
+        assert!(matches!(negated, Err(ParseError::UnknownPosixClass(name)) if name == "Alpha"));
+    }
+
+    #[test]
+    fn test_class_set_operations() {
+        // Positive test cases
+        test_matches("[a-z&&[^aeiou]]+", "rhythm"); // Consonants via intersection
+        test_matches("[a-z--[aeiou]]+", "crwth"); // Consonants via difference
+        test_matches("[a-c~~b-d]", "a"); // Symmetric difference keeps one-sided members
+        test_matches("[a-c~~b-d]", "d"); // Symmetric difference keeps one-sided members
+        test_matches(r"[\p{Greek}&&\p{Lu}]", "Λ"); // Greek and uppercase
+        test_matches("[[a-c][x-z]]", "y"); // Nested class union
+        test_matches(r"[\w--\d]+", "abc_"); // Word characters but not digits
+
+        // Negative test cases
+        test_matches("[a-z&&[^aeiou]]", "e"); // Vowel removed by intersection
+        test_matches("[a-z--[aeiou]]", "o"); // Vowel removed by difference
+        test_matches("[a-c~~b-d]", "b"); // Symmetric difference drops shared members
+        test_matches(r"[\p{Greek}&&\p{Lu}]", "λ"); // Lowercase Greek is excluded
+        test_matches(r"[\w--\d]", "5"); // Digit removed from word characters
+
+        // Edge cases
+        test_matches("[a&&b]", "a"); // Empty intersection matches nothing
+        test_matches("[^a-z&&[^x]]", "x"); // Negation applies to the whole expression
+        test_matches("[a-z&&b-y&&c-x]", "c"); // Operators chain left to right
+        test_matches("[a-z--b--c]", "b"); // Differences chain left to right
+        test_matches("[[^a]&&[a-c]]", "b"); // Negated nested class
+        test_matches("[[[a]]]", "a"); // Deeply nested class
+        test_matches("[a-]", "-"); // Trailing dash is still a literal
+        test_matches("[&]", "&"); // Lone ampersand is a literal
+        test_matches("[a~b]", "~"); // Lone tilde is a literal
+    }
+
+    #[test]
+    fn test_class_set_algebra() {
+        let letters = class::CharClass::new(vec![('a', 'z')]);
+        let vowels = class::CharClass::new(vec![('a', 'a'), ('e', 'e'), ('i', 'i'), ('o', 'o'), ('u', 'u')]);
+
+        let consonants = letters.difference(&vowels);
+        assert_eq!(consonants.intersect(&vowels), class::CharClass::empty());
+        assert_eq!(consonants.ranges()[..2], [('b', 'd'), ('f', 'h')]);
+
+        let mut union = consonants.clone();
+        union.union(&vowels);
+        assert_eq!(union, letters);
+        assert_eq!(letters.symmetric_difference(&vowels), consonants);
+    }
+
+    #[test]
+    fn test_anchors() {
+        // Positive test cases
+        test_matches("^abc", "abc"); // Start anchor at the start
+        test_matches("abc$", "abc"); // End anchor at the end
+        test_matches("^abc$", "abc"); // Both anchors
+        test_matches(r"\Aabc\z", "abc"); // Text anchors
+        test_matches("^$", ""); // Anchors on empty input
+        test_matches("(^a|b)+", "ab"); // Anchor inside a repeated group
+
+        // Negative test cases
+        test_matches("a^b", "ab"); // Start anchor in the middle
+        test_matches("a$b", "ab"); // End anchor in the middle
+        test_matches("a\n^b", "a\nb"); // Start anchor after a newline without multi-line
+        test_matches("a$\nb", "a\nb"); // End anchor before a newline without multi-line
+        test_matches("(^a|b)+", "ba"); // Anchor only holds at the start
+
+        // Edge cases
+        test_matches("^*a", "a"); // Repeated anchor
+        test_matches("a|^", ""); // Anchor as an alternative
+        test_matches(r"\^\$", "^$"); // Escaped anchors are literals
+        test_matches("[$^]+", "$^"); // Anchors are literals inside a class
+    }
+
+    #[test]
+    fn test_multi_line_anchors() {
+        // Positive test cases
+        test_matches_multi_line("a$\n^b", "a\nb"); // Line anchors around a newline
+        test_matches_multi_line("(^\\w+$\n?)+", "one\ntwo\nthree"); // Every line is a word
+        test_matches_multi_line("^$\n^$", "\n"); // Two empty lines
+        test_matches_multi_line(r"\Aa$\n^b\z", "a\nb"); // Text anchors next to line anchors
+
+        // Negative test cases
+        test_matches_multi_line("a^b", "ab"); // Start of line in the middle of a line
+        test_matches_multi_line("a$b", "ab"); // End of line in the middle of a line
+        test_matches_multi_line(r"a\n\Ab", "a\nb"); // Text anchor ignores multi-line
+        test_matches_multi_line(r"a\z\nb", "a\nb"); // Text anchor ignores multi-line
+
+        // Edge cases
+        test_matches_multi_line("(^\\w+$\n?)+", "one\ntwo words"); // A line with a space
+        test_matches_multi_line("^", ""); // Empty input is one empty line
+    }
+
+    #[test]
+    fn test_anchors_copy_simulation() {
+        let rregex = RRegexBuilder::new("a$\n^b").multi_line(true).build().unwrap();
+        assert!(rregex.matcher.copy_simulation("a\nb"));
+
+        let rregex = RRegex::new("a$\n^b".to_string()).unwrap();
+        assert!(!rregex.matcher.copy_simulation("a\nb"));
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        // Positive test cases
+        test_matches(r"\bfoo\b", "foo"); // Boundaries at both ends of a word
+        test_matches(r"foo\b bar", "foo bar"); // Boundary before a space
+        test_matches(r"a\Bb", "ab"); // No boundary inside a word
+        test_matches(r"-\B-", "--"); // No boundary between non-word characters
+        test_matches(r"\w+\b.\b\w+", "key=value"); // Boundaries around punctuation
+
+        // Negative test cases
+        test_matches(r"a\bb", "ab"); // No boundary inside a word
+        test_matches(r"a\B-", "a-"); // Boundary between a word and a non-word character
+        test_matches(r"\b-", "-"); // No boundary before a leading non-word character
+        test_matches(r"\b", ""); // Empty input has no boundary
+
+        // Edge cases
+        test_matches(r"\B", ""); // Empty input is not a boundary
+        test_matches(r"\bé\b", "é"); // Accented letters are word characters
+        test_matches(r"\bλ+\b", "λόγος"); // Greek letters are word characters
+        test_matches(r"x\b\u{300}", "x\u{300}"); // Combining marks are word characters
+    }
+
+    #[test]
+    fn test_word_start_and_end() {
+        // Positive test cases
+        test_matches(r"\<foo\>", "foo"); // Start and end of a word
+        test_matches(r"a \<b", "a b"); // Start after a space
+        test_matches(r"a\> b", "a b"); // End before a space
+        test_matches(r"\<é\>", "é"); // Accented letters are word characters
+
+        // Negative test cases
+        test_matches(r"a\<b", "ab"); // Not a start inside a word
+        test_matches(r"a\>b", "ab"); // Not an end inside a word
+        test_matches(r"\>a", "a"); // End is not a start
+        test_matches(r"a\<", "a"); // Start is not an end
+        test_matches(r"\<", ""); // Empty input has no word
+
+        // Edge cases
+        test_matches(r"-\<", "-"); // No word follows
+        test_matches(r"\>-", "-"); // No word precedes
+
+        let ascii = RRegexBuilder::new(r"\<é").unicode(false).build().unwrap();
+        assert!(!ascii.is_full_match("é"));
+    }
+
+    #[test]
+    fn test_ascii_word_boundaries() {
+        let unicode = RRegex::new(r"\bé\b".to_string()).unwrap();
+        assert!(unicode.is_full_match("é"));
+
+        let ascii = RRegexBuilder::new(r"\bé\b").unicode(false).build().unwrap();
+        assert!(!ascii.is_full_match("é"));
+
+        let ascii = RRegexBuilder::new(r"a\bé").unicode(false).build().unwrap();
+        assert!(ascii.is_full_match("aé"));
+        assert!(ascii.matcher.copy_simulation("aé"));
+
+        let ascii = RRegexBuilder::new(r"a\Bé").unicode(false).build().unwrap();
+        assert!(!ascii.is_full_match("aé"));
+    }
+
+    #[test]
+    fn test_unanchored_search() {
+        // Positive test cases
+        test_is_match("b", "abc"); // Literal in the middle
+        test_is_match("abc", "xxabcxx"); // Substring in the middle
+        test_is_match("a+", "bbbab"); // Repetition near the end
+        test_is_match(r"\d{4}", "year 2024!"); // Counted repetition inside text
+        test_is_match("foo|bar", "a bar b"); // Second alternative inside text
+        test_is_match(r"\bword\b", "a word here"); // Word boundaries inside text
+        test_is_match("c$", "abc"); // End anchor at the end
+
+        // Negative test cases
+        test_is_match("abc", "ab"); // Input shorter than the pattern
+        test_is_match("abc", "acb"); // Characters out of order
+        test_is_match("^b", "ab"); // Start anchor is only at the start
+        test_is_match("a$", "ab"); // End anchor is only at the end
+        test_is_match(r"\bword\b", "swordfish"); // Word boundaries rule out a substring
+
+        // Edge cases
+        test_is_match("", "abc"); // Empty pattern matches anywhere
+        test_is_match("x*", "abc"); // Nullable pattern matches anywhere
+        test_is_match("", ""); // Empty pattern on empty input
+        test_is_match("a", ""); // Non-empty pattern on empty input
+        test_is_match("aab", "aaab"); // Overlapping partial match
+        test_is_match("λ", "αβγλ"); // Multi-byte characters
+    }
+
+    #[test]
+    fn test_is_match_and_is_full_match() {
+        let rregex = RRegex::new("ab".to_string()).unwrap();
+
+        assert!(rregex.is_match("xaby"));
+        assert!(!rregex.is_full_match("xaby"));
+        assert!(rregex.is_match("ab"));
+        assert!(rregex.is_full_match("ab"));
+    }
+
+    #[test]
+    fn test_leftmost_first_find() {
+        // Positive test cases
+        test_find("b", "abc"); // Literal in the middle
+        test_find("a+", "baaab"); // Greedy repetition takes every 'a'
+        test_find(r"\d+", "abc 123 456"); // Leftmost of several matches
+        test_find("abc", "ababc"); // Match after a failed partial match
+        test_find(r"\b\w+\b", "  hello world"); // First word
+
+        // Negative test cases
+        test_find("x", "abc"); // No match
+        test_find("^b", "ab"); // Anchor rules out the only candidate
+        test_find("abc", "ab"); // Input ends before the match does
+
+        // Edge cases
+        test_find("a|ab", "ab"); // Leftmost-first prefers the first alternative
+        test_find("ab|a", "ab"); // Leftmost-first prefers the first alternative
+        test_find("a*", "baaa"); // Empty match at the start beats a longer one later
+        test_find("(a|ab)(c|bcd)", "abcd"); // Priority decides between parses
+        test_find("a?", ""); // Empty match on empty input
+        test_find("", "abc"); // Empty pattern matches at the start
+        test_find(r"\w+", "ßλ😀ab"); // Byte offsets around multi-byte characters
+        test_find(r"b\b", "ab ab"); // Assertion at the end of the match
+        test_find("(a|b)*c", "abababx abc"); // Restart after a long failed run
+    }
+
+    #[test]
+    fn test_match_accessors() {
+        let rregex = RRegex::new(r"\d+".to_string()).unwrap();
+        let found = rregex.find("order 66 now").unwrap();
+
+        assert_eq!(found.start(), 6);
+        assert_eq!(found.end(), 8);
+        assert_eq!(found.range(), 6..8);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found.as_str(), "66");
+        assert!(!found.is_empty());
+    }
+
+    #[test]
+    fn test_non_overlapping_matches() {
+        // Positive test cases
+        test_find_iter("a", "banana"); // Every occurrence of a literal
+        test_find_iter(r"\d+", "1 22 333"); // Several greedy matches
+        test_find_iter(r"\w+", "hello, wide world"); // Words between punctuation
+        test_find_iter("ab|a", "abaab"); // Alternation at each restart
+
+        // Negative test cases
+        test_find_iter("x", "banana"); // No matches at all
+        test_find_iter("^a", "aaa"); // Start anchor only matches once
+
+        // Edge cases
+        test_find_iter("a*", "baaab"); // Empty matches around a non-empty one
+        test_find_iter("a*", ""); // Single empty match on empty input
+        test_find_iter("", "abc"); // Empty match at every position
+        test_find_iter("", "λ😀é"); // Empty matches never split a character
+        test_find_iter("a|", "aab"); // Empty alternative after non-empty matches
+        test_find_iter(r"\b", "ab cd"); // Every word boundary
+        test_find_iter("$", "abc"); // End anchor at the end only
+        test_find_iter(r"x*", "λxλ"); // Empty matches between multi-byte characters
+    }
+
+    #[test]
+    fn test_find_iter_as_str() {
+        let rregex = RRegex::new(r"\w+".to_string()).unwrap();
+        let words: Vec<_> = rregex.find_iter("one, two; three").map(|m| m.as_str()).collect();
+
+        assert_eq!(words, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn test_capture_groups() {
+        // Positive test cases
+        test_captures("(a)(b)", "ab"); // Two groups in sequence
+        test_captures(r"(\d{4})-(\d{2})-(\d{2})", "on 2024-06-30"); // Date parts
+        test_captures("((a)b)", "ab"); // Nested groups are numbered by their open paren
+        test_captures("(a|b)+", "abba"); // Repeated group keeps its last iteration
+        test_captures(r"(\w+)@(\w+)\.com", "mail bob@example.com now"); // Unanchored groups
+
+        // Negative test cases
+        test_captures("(a)(b)", "ba"); // No match, no captures
+        test_captures("(x)", "abc"); // No match anywhere
+
+        // Edge cases
+        test_captures("(a)|(b)", "b"); // Group in the untaken alternative is unset
+        test_captures("(a)?b", "b"); // Skipped optional group is unset
+        test_captures("(a*)*", "b"); // Empty iteration of a nested star
+        test_captures("(a*)+", "b"); // Empty iteration of a plus
+        test_captures("(a|ab)(c|bcd)(d*)", "abcd"); // Leftmost-first priority between groups
+        test_captures("(a*)(a*)", "aaa"); // First greedy group takes everything
+        test_captures("(a){2}", "aa"); // Counted repetition shares one group
+        test_captures("()", ""); // Empty group on empty input
+        test_captures(r"(\b)(\w+)", " ab"); // Group around an assertion
+        test_captures("(λ)(😀)", "xλ😀"); // Byte offsets of multi-byte characters
+    }
+
+    #[test]
+    fn test_captures_accessors() {
+        let rregex = RRegex::new(r"(\w+)=(\d+)?".to_string()).unwrap();
+        let caps = rregex.captures("key=").unwrap();
+
+        assert_eq!(caps.len(), 3);
+        assert_eq!(caps.get(0).unwrap().as_str(), "key=");
+        assert_eq!(caps.get(1).unwrap().as_str(), "key");
+        assert!(caps.get(2).is_none());
+        assert!(caps.get(3).is_none());
+
+        // A group that is repeated zero times still counts, but never matches
+        let rregex = RRegex::new("(a){0}b".to_string()).unwrap();
+        let caps = rregex.captures("b").unwrap();
+
+        assert_eq!(caps.len(), 2);
+        assert!(caps.get(1).is_none());
+    }
+
+    #[test]
+    fn test_named_groups() {
+        // Positive test cases
+        test_captures(r"(?P<year>\d{4})-(?P<month>\d{2})", "on 2024-06"); // Python-style names
+        test_captures(r"(?<word>\w+) (\w+)", "hello world"); // Named and unnamed groups share numbering
+        test_captures("(?<outer>a(?<inner>b))", "ab"); // Nested named groups
+
+        // Negative test cases
+        test_captures("(?<x>a)", "b"); // No match, no captures
+
+        // Edge cases
+        test_captures("(?<x>a)|(?<y>b)", "b"); // Named group in the untaken alternative
+        test_captures("(?<_1>)", ""); // Empty named group with an underscore name
+    }
+
+    #[test]
+    fn test_group_names() {
+        let rregex = RRegex::new(r"(?P<key>\w+)=(\d+)?(?<rest>.*)".to_string()).unwrap();
+        let names: Vec<_> = rregex.capture_names().collect();
+        assert_eq!(names, [None, Some("key"), None, Some("rest")]);
+
+        let caps = rregex.captures("a=").unwrap();
+        assert_eq!(caps.name("key").unwrap().as_str(), "a");
+        assert_eq!(caps.name("rest").unwrap().as_str(), "");
+        assert!(caps.name("missing").is_none());
+
+        // Invalid and duplicate names are rejected
+        for pattern in ["(?<>a)", "(?<1a>a)", "(?<a-b>a)", "(?P<a", "(?<a b>c)"] {
+            assert!(matches!(RRegex::new(pattern.to_string()), Err(ParseError::InvalidGroupName(_))), "{pattern}");
+            assert!(regex::Regex::new(pattern).is_err(), "{pattern}");
+        }
+        assert!(matches!(RRegex::new("(?<a>x)(?<a>y)".to_string()), Err(ParseError::DuplicateGroupName(name)) if name == "a"));
+        assert!(matches!(RRegex::new("(?<a>x(?P<a>y))".to_string()), Err(ParseError::DuplicateGroupName(_))));
+    }
+
+    #[test]
+    fn test_non_capturing_groups() {
+        // Positive test cases
+        test_captures("(?:a|b)+(c)", "abbc"); // Non-capturing group takes no index
+        test_captures("(?:(a)|b)(?:c)", "ac"); // Capture inside a non-capturing group
+        test_captures("(?:ab){2}", "xabab"); // Counted repetition of a non-capturing group
+
+        // Negative test cases
+        test_captures("(?:ab)+", "ba"); // No match
+
+        // Edge cases
+        test_captures("(?:)", "a"); // Empty non-capturing group
+        test_captures("(?:a|)b", "b"); // Empty alternative inside
+    }
+
+    #[test]
+    fn test_inline_flags() {
+        // Positive test cases
+        test_find("(?s).", "\n"); // Dot matches new line for the rest of the pattern
+        test_find("(?s:.)", "\n"); // Dot matches new line in a scoped group
+        test_find("(?m)^b$", "a\nb\nc"); // Multi-line anchors
+        test_find("(?m)^a|^b", "x\nb"); // Flags carry across alternatives
+        test_find("a(?m:$)", "a\nb"); // Scoped multi-line anchor
+        test_find("(?ms)a.^b", "a\nb"); // Several flags at once
+
+        // Negative test cases
+        test_find("(?s:a).", "a\n"); // Scoped flag ends with its group
+        test_find("((?m)^b)$", "b\nc"); // Flag set inside a group ends with it
+        test_find("(?s)(?-s).", "\n"); // Later flags clear earlier ones
+        test_find("(?m-s:^.)", "\n"); // Set and clear in one group
+
+        // Edge cases
+        test_find("(?m)", "abc"); // Flags alone match the empty string
+        test_find("(?s)a|.", "\n"); // Flags set in the first alternative stay on
+    }
+
+    #[test]
+    fn test_invalid_groups() {
+        let patterns = [
+            "(?)", "(?-)", "(?i-)", "(?-:a)", "(?mm)", "(?m-m)", // Empty, dangling or repeated flags
+            "(?q)", "(?m", "a(?", // Unknown or unterminated flags
+            "a)b", "(a))", ")", // Unopened groups
+            "(?:a", "((?m)a", // Unclosed groups
+        ];
+        for pattern in patterns {
+            assert!(RRegex::new(pattern.to_string()).is_err(), "{pattern}");
+            assert!(regex::Regex::new(pattern).is_err(), "{pattern}");
+        }
+
+        assert!(matches!(RRegex::new("(?q)".to_string()), Err(ParseError::UnknownFlag('q'))));
+        assert!(matches!(RRegex::new("a(?m-)".to_string()), Err(ParseError::InvalidFlags(Span { start: 1, end: 6 }))));
+        assert!(matches!(RRegex::new("a)".to_string()), Err(ParseError::MismatchedParentheses)));
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        // Positive test cases
+        test_find("(?i)hello", "say HeLLo"); // ASCII letters
+        test_find("(?i)k", "\u{212A}"); // Kelvin sign folds with k
+        test_find("(?i)K", "\u{212A}"); // and with K
+        test_find("(?i)s", "\u{17F}"); // Long s folds with s
+        test_find("(?i)σ", "ΣΑΣ ς"); // Greek sigma and final sigma
+        test_find("(?i)привет", "ПРИВЕТ"); // Cyrillic
+        test_find("(?i)ա", "Ա"); // Armenian
+        test_find("(?i)ა", "Ა"); // Georgian Mkhedruli and Mtavruli
+        test_find("(?i)\u{10428}", "\u{10400}"); // Deseret, outside the BMP
+        test_find("(?i)[a-c]+", "xAbC"); // Ranges fold
+        test_find("(?i)[k]", "\u{212A}"); // Classes fold to the whole orbit
+        test_find(r"(?i)\p{Lu}+", "abcDEF"); // Unicode classes fold
+        test_find("(?i:a)b", "Ab"); // Scoped flag
+
+        // Negative test cases
+        test_find("(?i:a)b", "AB"); // Flag ends with its group
+        test_find("(?i)a(?-i)b", "AB"); // Flag cleared
+        test_find("(?i)[^k]", "K\u{212A}k"); // Negation after folding excludes the orbit
+        test_find(r"(?i)\P{Lu}", "aA"); // Negated Unicode class folds first
+        test_find("((?i)a)b", "AB"); // Flag set inside a group ends with it
+
+        // Edge cases
+        test_find("(?i)1", "1"); // Characters without case are unchanged
+        test_find("(?i)[k--K]", "kK\u{212A}"); // Set operations fold their operands first
+        test_find("(?i)[a-z--AEIOU]", "ae"); // Difference with a folded right-hand side
+        test_find("(?i)[a-c~~B-D]", "bc"); // Symmetric difference of folded operands
+        test_find(r"(?i)[\w--k]", "Kk\u{212A}"); // Difference from a folded escape
+        test_find("(?i)ǅ", "Ǆ"); // Title case orbit
+        test_find(r"(?i)\x{212A}", "k"); // Escaped literals fold too
+    }
+
+    #[test]
+    fn test_case_insensitive_builder() {
+        let rregex = RRegexBuilder::new("straße").case_insensitive(true).build().unwrap();
+        assert!(rregex.is_match("STRAßE"));
+        assert!(!rregex.is_match("STRASSE")); // Simple folding only
+
+        let rregex = RRegexBuilder::new("a(?-i)b").case_insensitive(true).build().unwrap();
+        assert!(rregex.is_match("Ab"));
+        assert!(!rregex.is_match("AB"));
+
+        // Without Unicode only ASCII letters fold
+        let rregex = RRegexBuilder::new("k").case_insensitive(true).unicode(false).build().unwrap();
+        assert!(rregex.is_match("K"));
+        assert!(!rregex.is_match("\u{212A}"));
+    }
+
+    #[test]
+    fn test_extended_mode() {
+        // Positive test cases
+        test_find("(?x) a b c ", "abc"); // Whitespace is ignored
+        test_find("(?x)a # letter a\n b # then b", "ab"); // Comments run to the end of the line
+        test_find("(?x)a\n\t+", "aa"); // Whitespace between an atom and its postfix
+        test_find("(?x)a{ 2 , 3 }", "aaaa"); // Whitespace inside counted repetitions
+        test_find(r"(?x)a\ b", "a b"); // Escaped space is a literal
+        test_find(r"(?x)a\#b", "a#b"); // Escaped '#' is a literal
+        test_find("(?x)(?<word> \\w+ )", "  hi"); // Named group
+
+        // Negative test cases
+        test_find("(?x)a b", "a b"); // Literal space is not matched
+        test_find("(?x:a b) c", "ab c"); // Flag ends with its group
+        test_find("(?x)a (?-x) b", "ab"); // Flag cleared
+
+        // Edge cases
+        test_find("(?x)#", "#"); // Pattern that is only a comment
+        test_find("(?x)a#", "a"); // Comment without a trailing new line
+        test_find("(?x)( a | b )+", "ba"); // Alternation and groups
+    }
+
+    #[test]
+    fn test_extended_mode_builder() {
+        let pattern = r"
+            (?<year>\d{4}) - # year
+            (?<month>\d{2})  # month
+        ";
+        let rregex = RRegexBuilder::new(pattern).ignore_whitespace(true).build().unwrap();
+        let caps = rregex.captures("on 2024-06").unwrap();
+        assert_eq!(caps.name("year").unwrap().as_str(), "2024");
+        assert_eq!(caps.name("month").unwrap().as_str(), "06");
+
+        // Error spans are offsets into the pattern as written
+        let error = RRegexBuilder::new("a  # comment\n  \\xZ").ignore_whitespace(true).build();
+        assert!(matches!(error, Err(ParseError::InvalidHexEscape(Span { start: 15, end: 17 }))));
+
+        // Whitespace inside a class is kept, unlike in the regex crate
+        let rregex = RRegexBuilder::new("[a b]").ignore_whitespace(true).build().unwrap();
+        assert!(rregex.is_match(" "));
+    }
+
+    #[test]
+    fn test_lazy_quantifiers() {
+        // Positive test cases
+        test_find("a*?", "aaa"); // Lazy star matches empty
+        test_find("a+?", "aaa"); // Lazy plus matches once
+        test_find("a??", "a"); // Lazy optional skips
+        test_find("a{2,4}?", "aaaa"); // Lazy counted repetition takes the minimum
+        test_find("a{2,}?", "aaaa"); // Lazy open-ended repetition
+        test_find("<.+?>", "<a><b>"); // Shortest field between delimiters
+        test_find("a.*?b", "a1b2b"); // Lazy star stops at the first b
+        test_captures("(a+?)(a*)", "aaa"); // Lazy group leaves the rest to the next one
+        test_captures(r"(\w+?)(\d*)$", "abc123"); // Lazy group still extends to reach the end
+        test_captures("(a|b)*?c", "abc"); // Lazy star iterates when it must
+
+        // Negative test cases
+        test_find("a+?b", "aac"); // No match
+        test_find("x*?y", "xxx"); // Lazy star cannot skip a required y
+
+        // Edge cases
+        test_find("a{3}?", "aaaa"); // Lazy exact repetition is the same as greedy
+        test_find("(?:a*?)*?b", "aab"); // Nested lazy stars
+        test_captures("(a??)(a)", "a"); // Lazy optional yields to the next group
+        test_find_iter("a+?", "aaa"); // Each match takes one character
+        test_find_iter(r"\d*?", "12"); // Empty lazy matches
+    }
+
+    fn test_backtracker_captures(s1: &str, s2: &str) {
+        let expected: Option<Vec<_>> = regex::Regex::new(s1).unwrap().captures(s2)
+            .map(|caps| caps.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect());
+
+        let nfa = Parser::new(&mut Lexer::new(s1.to_string())).unwrap().parse().unwrap();
+        let slot_count = 2 * nfa.group_count;
+        let found: Option<Vec<_>> = backtrack::Backtracker::new(&nfa, builder::DEFAULT_BACKTRACK_LIMIT)
+            .captures_at(s2, 0, slot_count, false)
+            .unwrap()
+            .map(|slots| slots.chunks(2).map(|pair| pair[0].zip(pair[1])).collect());
+        assert_eq!(found, expected, "Backtracker test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    #[test]
+    fn test_backtracker_agrees_with_automata() {
+        test_backtracker_captures("(a|ab)(c|bcd)(d*)", "abcd"); // Leftmost-first priority
+        test_backtracker_captures("(a*)*", "b"); // Empty loop is cut
+        test_backtracker_captures("(a*)+$", "aa"); // Empty iteration after a real one
+        test_backtracker_captures(r"(\w+?)(\d*)$", "abc123"); // Lazy group
+        test_backtracker_captures(r"\b(\w+)\b", "  hi"); // Assertions
+        test_backtracker_captures("(?m)^(b)$", "a\nb"); // Multi-line anchors
+        test_backtracker_captures("(x)", "abc"); // No match
+        test_backtracker_captures("(λ)(😀)", "xλ😀"); // Multi-byte characters
+    }
+
+    #[test]
+    fn test_backreferences() {
+        let rregex = RRegex::new(r"\b(\w+) \1\b".to_string()).unwrap();
+        assert_eq!(rregex.find("this is is a test").unwrap().as_str(), "is is"); // Repeated word
+        assert!(!rregex.is_match("this is a test"));
+
+        let rregex = RRegex::new(r"<(?<tag>\w+)>.*?</\k<tag>>".to_string()).unwrap();
+        let caps = rregex.captures("x <b>bold <i>it</i></b> y").unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "<b>bold <i>it</i></b>"); // Paired tags
+        assert_eq!(caps.name("tag").unwrap().as_str(), "b");
+        assert!(!rregex.is_match("<b>bold</i>"));
+
+        let rregex = RRegex::new(r"(a|b)\1+".to_string()).unwrap();
+        let found: Vec<_> = rregex.find_iter("aabbbab").map(|m| m.as_str()).collect();
+        assert_eq!(found, ["aa", "bbb"]); // find_iter goes through the backtracker
+
+        assert!(RRegex::new(r"(a*)\1".to_string()).unwrap().is_full_match("aaaa")); // Full match backtracks into the group
+        assert!(!RRegex::new(r"(a*)\1".to_string()).unwrap().is_full_match("aaa"));
+        assert!(!RRegex::new(r"(a)?b\1".to_string()).unwrap().is_match("b")); // Unset group never matches
+        assert!(RRegex::new(r"(?i)(a)\1".to_string()).unwrap().is_match("aA")); // Case-insensitive
+        assert!(RRegex::new(r"(?i)(k)\1".to_string()).unwrap().is_match("k\u{212A}"));
+        assert!(!RRegex::new(r"(a)\1".to_string()).unwrap().is_match("aA"));
+        assert!(RRegex::new(r"(a)(b)(c)(d)(e)(f)(g)(h)(i)(j)\10".to_string()).unwrap().is_match("abcdefghijj")); // Multi-digit
+
+        // Backreferences must name an opened group
+        assert!(matches!(RRegex::new(r"\1(a)".to_string()), Err(ParseError::InvalidBackreference(1))));
+        assert!(matches!(RRegex::new(r"(a)\2".to_string()), Err(ParseError::InvalidBackreference(2))));
+        assert!(matches!(RRegex::new(r"\k<x>(?<x>a)".to_string()), Err(ParseError::UnknownGroupName(_))));
+        assert!(matches!(RRegex::new(r"(a)\k1".to_string()), Err(ParseError::InvalidEscape('k'))));
+    }
+
+    #[test]
+    fn test_backtrack_limit() {
+        let pattern = r"(a|aa)*\1b";
+        let input = "a".repeat(40);
+
+        let rregex = RRegexBuilder::new(pattern).backtrack_limit(10_000).build().unwrap();
+        assert_eq!(rregex.try_is_match(&input), Err(MatchError::BacktrackLimitExceeded(10_000)));
+        assert!(rregex.try_find(&input).is_err());
+        assert!(rregex.try_captures(&input).is_err());
+        assert!(rregex.try_is_full_match(&input).is_err());
+
+        // Within the budget the same pattern answers normally
+        assert_eq!(rregex.try_is_match("aab"), Ok(true));
+
+        // Patterns without backreferences never touch the budget
+        let rregex = RRegexBuilder::new("(a|aa)*b").backtrack_limit(0).build().unwrap();
+        assert_eq!(rregex.try_is_match(&input), Ok(false));
+    }
+
+    // Checks a look-around pattern against the expected match span, on the
+    // automaton path and on the backtracker.
+    fn test_look(s1: &str, s2: &str, expected: Option<&str>) {
+        let rregex = RRegex::new(s1.to_string()).unwrap();
+        assert_eq!(rregex.find(s2).map(|m| m.as_str()), expected, "Look-around test failed for regex: '{s1}', input: '{s2}'");
+        assert_eq!(rregex.is_match(s2), expected.is_some());
+
+        let nfa = Parser::new(&mut Lexer::new(s1.to_string())).unwrap().parse().unwrap();
+        let found = backtrack::Backtracker::new(&nfa, builder::DEFAULT_BACKTRACK_LIMIT)
+            .captures_at(s2, 0, 2, false)
+            .unwrap()
+            .map(|slots| &s2[slots[0].unwrap()..slots[1].unwrap()]);
+        assert_eq!(found, expected, "Backtracker look-around test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    #[test]
+    fn test_lookahead() {
+        // Positive test cases
+        test_look(r"\w+(?=!)", "hey you!", Some("you")); // Followed by
+        test_look(r"\d+(?!px)\b", "12px 34 em", Some("34")); // Not followed by
+        test_look(r"(?=.*\d)(?=.*[a-z])\w{6,}", "abc123", Some("abc123")); // Password-style rules
+        test_look(r"a(?=b|c)", "ac", Some("a")); // Alternation inside
+        test_look(r"(?=(a+))a", "aaa", Some("a")); // Lookahead doesn't consume
+
+        // Negative test cases
+        test_look(r"(?=.*\d)(?=.*[a-z])\w{6,}", "abcdef", None); // Missing a digit
+        test_look(r"foo(?!bar)", "foobar", None); // Excluded continuation
+        test_look(r"a(?=b)", "a", None); // Nothing follows
+
+        // Edge cases
+        test_look(r"(?=)", "", Some("")); // Empty lookahead always holds
+        test_look(r"(?!)", "a", None); // Empty negative lookahead never holds
+        test_look(r"a(?=$)", "ba", Some("a")); // Assertion inside
+        test_look(r"(?=a(?=b))\w", "acab", Some("a")); // Nested lookahead
+        test_look(r"(?i)x(?=Y)", "XY", Some("X")); // Flags apply inside
+    }
+
+    #[test]
+    fn test_lookbehind() {
+        // Positive test cases
+        test_look(r"(?<=\$)\d+", "cost: $42", Some("42")); // Preceded by
+        test_look(r"(?<!\$)\b\d+", "$42 17", Some("17")); // Not preceded by
+        test_look(r"(?<=a+)b", "aaab", Some("b")); // Unbounded lookbehind
+        test_look(r"(?<=ab|c)d", "cd", Some("d")); // Alternatives of different lengths
+
+        // Negative test cases
+        test_look(r"(?<=x)y", "ay", None); // Wrong predecessor
+        test_look(r"(?<!a)b", "ab", None); // Excluded predecessor
+
+        // Edge cases
+        test_look(r"(?<=^)a", "a", Some("a")); // Anchor inside
+        test_look(r"(?<!.)a", "ba a", None); // Every a here has a predecessor
+        test_look(r"(?<=😀)λ", "😀λ", Some("λ")); // Multi-byte characters
+        test_look(r"(?<=(?<!a)b)c", "abc bc", Some("c")); // Nested look-behind
+        test_look(r"\w+(?<=ing)\b", "sing along", Some("sing")); // Lookbehind after consuming
+    }
+
+    #[test]
+    fn test_find_iter_with_look_around_on_long_haystack() {
+        // The look-around tables are built once for the haystack, not once per match
+        let haystack = "#a ".repeat(4000);
+
+        let rregex = RRegex::new(r"(?<=#)\w".to_string()).unwrap();
+        assert_eq!(rregex.find_iter(&haystack).count(), 4000);
+
+        let rregex = RRegex::new(r"\w(?= )".to_string()).unwrap();
+        assert_eq!(rregex.find_iter(&haystack).count(), 4000);
+    }
+
+    #[test]
+    fn test_look_around_with_other_features() {
+        // Look-around beside a backreference runs on the backtracker
+        let rregex = RRegex::new(r"(\w)(?=\1)".to_string()).unwrap();
+        assert_eq!(rregex.find("abccd").unwrap().start(), 2);
+
+        // Groups inside a look-around take an index but never capture
+        let rregex = RRegex::new(r"(?=(\w+))(\w)".to_string()).unwrap();
+        let caps = rregex.captures("hi").unwrap();
+        assert_eq!(caps.len(), 3);
+        assert!(caps.get(1).is_none());
+        assert_eq!(caps.get(2).unwrap().as_str(), "h");
+
+        // Full matches and iteration see look-around too
+        assert!(RRegex::new(r"\w+(?<!s)".to_string()).unwrap().is_full_match("word"));
+        assert!(!RRegex::new(r"\w+(?<!s)".to_string()).unwrap().is_full_match("words"));
+        let rregex = RRegex::new(r"(?<=#)\w+".to_string()).unwrap();
+        let tags: Vec<_> = rregex.find_iter("#a b #cd").map(|m| m.as_str()).collect();
+        assert_eq!(tags, ["a", "cd"]);
+
+        // Look-around sub-patterns count towards the size limit
+        assert!(RRegexBuilder::new("(?=a{100})").size_limit(50).build().is_err());
+    }
+
+    // Checks an atomic or possessive pattern against the expected match, on
+    // the automaton path and on the backtracker.
+    fn test_atomic(s1: &str, s2: &str, expected: Option<&str>) {
+        let rregex = RRegex::new(s1.to_string())
+            .unwrap_or_else(|e| panic!("Failed to create regex ('{s1}'): {e}"));
+        assert_eq!(rregex.find(s2).map(|m| m.as_str()), expected, "Atomic test failed for regex: '{s1}', input: '{s2}'");
+
+        let nfa = Parser::new(&mut Lexer::new(s1.to_string())).unwrap().parse().unwrap();
+        let found = backtrack::Backtracker::new(&nfa, builder::DEFAULT_BACKTRACK_LIMIT)
+            .captures_at(s2, 0, 2, false)
+            .unwrap()
+            .map(|slots| &s2[slots[0].unwrap()..slots[1].unwrap()]);
+        assert_eq!(found, expected, "Backtracker atomic test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    #[test]
+    fn test_possessive_quantifiers() {
+        // Positive test cases
+        test_atomic("a++b", "aaab", Some("aaab")); // Possessive plus
+        test_atomic(r"\d*+", "123x", Some("123")); // Possessive star
+        test_atomic("x?+y", "xy", Some("xy")); // Possessive optional
+        test_atomic("[a-z]{2,}+1", "ab1", Some("ab1")); // Possessive open-ended repetition
+        test_atomic("a{3}+", "aaaa", Some("aaa")); // Exact repetition is fixed-length
+
+        // Negative test cases
+        test_atomic("a*+a", "aaa", None); // The star keeps every a
+        test_atomic("x?+x", "x", None); // The optional keeps the x
+        test_atomic(".*+x", "abx", None); // Dot star runs to the end
+        test_atomic("[ab]++b", "aab", None); // Class plus keeps the b
+
+        // Edge cases
+        test_atomic("a*+b", "b", Some("b")); // Possessive star matching nothing
+        test_atomic("x?+x", "xx", Some("xx")); // Second x after the kept one
+        test_atomic("a++", "baa", Some("aa")); // Unanchored search
+        test_atomic("(a++)b", "aab", Some("aab")); // Inside a group
+    }
+
+    #[test]
+    fn test_atomic_groups() {
+        // Positive test cases
+        test_atomic("(?>abc|xyz)d", "xyzd", Some("xyzd")); // Fixed-length alternatives
+        test_atomic("(?>a{2})b", "aab", Some("aab")); // Fixed-length repetition
+        test_atomic(r"(?>\d)+", "12", Some("12")); // Repeated atomic group
+        test_atomic("(?>a(?=b))b", "ab", Some("ab")); // Look-around inside
+
+        // Negative test cases
+        test_atomic("(?>abc)d", "abce", None); // No match
+
+        // Edge cases
+        test_atomic("(?>)a", "a", Some("a")); // Empty atomic group
+        test_atomic("(?>(?>a)b)", "ab", Some("ab")); // Nested atomic groups
+
+        // Captures inside an atomic group are kept
+        let caps = RRegex::new("(?>(a)(b))c".to_string()).unwrap().captures("abc").unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "a");
+        assert_eq!(caps.get(2).unwrap().as_str(), "b");
+    }
+
+    #[test]
+    fn test_atomic_groups_on_backtracker() {
+        // Groups whose matches could differ in length are rejected on the automaton path
+        for pattern in ["(?>a|ab)c", "(?>a+)b", "(?:ab)++", "a{2,4}+", "(a)*+", "(?=(?>a|ab))"] {
+            assert!(matches!(RRegex::new(pattern.to_string()), Err(ParseError::UnsupportedAtomicGroup)), "{pattern}");
+        }
+
+        // With a backreference the whole pattern runs on the backtracker, which never
+        // goes back into an atomic group
+        let atomic = RRegex::new(r"(x)\1(?>a|ab)c".to_string()).unwrap();
+        let plain = RRegex::new(r"(x)\1(?:a|ab)c".to_string()).unwrap();
+        assert!(!atomic.is_match("xxabc"));
+        assert!(plain.is_match("xxabc"));
+        assert!(atomic.is_match("xxac"));
+
+        let rregex = RRegex::new(r"(\w)(?>\1+)\1".to_string()).unwrap();
+        assert!(!rregex.is_match("aaa")); // The atomic group keeps every repeat
+        assert!(RRegex::new(r"(\w)\1+\1".to_string()).unwrap().is_match("aaa"));
+
+        let rregex = RRegex::new(r"(?>(a+))\1".to_string()).unwrap();
+        assert_eq!(rregex.find("aaaa").map(|m| m.as_str()), None); // Captured a's can't be given back
+        let rregex = RRegex::new(r"(?>(a+)b)\1".to_string()).unwrap();
+        assert_eq!(rregex.find("aabaa").map(|m| m.as_str()), Some("aabaa"));
+    }
+
+    fn leftmost_longest(pattern: &str) -> RRegex {
+        RRegexBuilder::new(pattern).match_kind(MatchKind::LeftmostLongest).build().unwrap()
+    }
+
+    fn test_longest(s1: &str, s2: &str, expected: Option<&str>) {
+        let found = leftmost_longest(s1).find(s2).map(|m| m.as_str());
+        assert_eq!(found, expected, "Leftmost-longest test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    fn test_posix(s1: &str, s2: &str, expected: &[Option<(usize, usize)>]) {
+        let caps = leftmost_longest(s1).captures(s2).unwrap();
+        let groups: Vec<_> = caps.iter().map(|m| m.map(|m| (m.start(), m.end()))).collect();
+        assert_eq!(groups, expected, "POSIX captures test failed for regex: '{s1}', input: '{s2}'");
+    }
+
+    #[test]
+    fn test_leftmost_longest() {
+        // Positive test cases
+        test_longest("a|ab", "ab", Some("ab")); // Longer alternative wins
+        test_longest("sam|samwise", "samwise", Some("samwise")); // Regardless of order
+        test_longest("(?:a|ab)(?:c|bcd)", "abcd", Some("abcd")); // Longest overall
+        test_longest(r"\bin\b|\binside\b", "inside", Some("inside")); // Assertions
+        test_longest(r"a+?", "aaa", Some("aaa")); // Lazy quantifiers are greedy
+        test_longest(r"\w+(?=!)|\w+!", "hey!", Some("hey!")); // Look-around
+
+        // Negative test cases
+        test_longest("a|ab", "c", None); // No match
+
+        // Edge cases
+        test_longest("x*", "yx", Some("")); // Leftmost beats longer
+        test_longest("b|abc", "abc", Some("abc")); // Earlier start wins
+        test_longest("", "abc", Some("")); // Empty pattern
+
+        let rregex = leftmost_longest("a|ab");
+        let found: Vec<_> = rregex.find_iter("abab").map(|m| m.as_str()).collect();
+        assert_eq!(found, ["ab", "ab"]);
+        assert_eq!(RRegex::new("a|ab".to_string()).unwrap().find("ab").map(|m| m.as_str()), Some("a")); // Default is unchanged
+    }
+
+    #[test]
+    fn test_leftmost_longest_agrees_with_full_match() {
+        // The leftmost-longest match is the first start, then the last end, at
+        // which the pattern matches the whole substring
+        let patterns = ["a|ab", "(a|ab)(c|bcd)", "a*?b?", "(?:ab|a)*b", "x{1,3}?y?", "[ab]+|ba"];
+        let inputs = ["", "ab", "abcd", "aabab", "xxxy", "ba", "cab"];
+
+        for pattern in patterns {
+            let longest = leftmost_longest(pattern);
+            let full = RRegex::new(pattern.to_string()).unwrap();
+
+            for input in inputs {
+                let expected = (0..=input.len()).find_map(|start| {
+                    (start..=input.len()).rev().find(|&end| full.is_full_match(&input[start..end])).map(|end| &input[start..end])
+                });
+                assert_eq!(longest.find(input).map(|m| m.as_str()), expected, "{pattern} on {input}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_posix_captures() {
+        // Positive test cases
+        test_posix("(a|ab)(c|bcd)(d*)", "abcd", &[Some((0, 4)), Some((0, 2)), Some((2, 3)), Some((3, 4))]); // Earlier groups take the most
+        test_posix("(a|ab)(bc|c)", "abc", &[Some((0, 3)), Some((0, 2)), Some((2, 3))]); // Not the first alternative
+        test_posix(r"(.*)(\d+)", "ab12", &[Some((0, 4)), Some((0, 3)), Some((3, 4))]); // Greedy prefix
+        test_posix("(a|b)*", "ab", &[Some((0, 2)), Some((1, 2))]); // Last iteration
+        test_posix("(a{1,2}){2}", "aaa", &[Some((0, 3)), Some((2, 3))]); // Longest first iteration
+        test_posix("(?<x>a|ab)(?:b)?", "ab", &[Some((0, 2)), Some((0, 2))]); // Named groups
+
+        // Negative test cases
+        assert!(leftmost_longest("(a|ab)(c|bcd)").captures("abd").is_none()); // No match
+
+        // Edge cases
+        test_posix("(a?)((ab)?)(b?)", "ab", &[Some((0, 2)), Some((0, 1)), Some((1, 1)), None, Some((1, 2))]); // Empty group, skipped group
+        test_posix("(a|(b))*", "ba", &[Some((0, 2)), Some((1, 2)), None]); // Reset in each iteration
+        test_posix("(a*)*", "b", &[Some((0, 0)), Some((0, 0))]); // One null iteration of an empty match
+        test_posix("(a|b)*", "c", &[Some((0, 0)), None]); // No null iteration if the body cannot be empty
+        test_posix("(a*)+", "b", &[Some((0, 0)), Some((0, 0))]); // One required empty iteration
+        test_posix("(a)(?=(b))", "ab", &[Some((0, 1)), Some((0, 1)), None]); // Groups in look-arounds are never set
+    }
+
+    #[test]
+    fn test_posix_null_subexpressions() {
+        // From the nullsubexpr cases of Fowler's POSIX regex tests
+        test_posix("(a*)*", "a", &[Some((0, 1)), Some((0, 1))]);
+        test_posix("(a*)*", "x", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("(a*)*", "aaaaaax", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("(a*)+", "x", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("(a*)+", "aaaaaax", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("(a+)*", "x", &[Some((0, 0)), None]);
+        test_posix("(a+)*", "aaaaaax", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("(a+)+", "aaaaaax", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("([a]*)*", "x", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("([a]*)+", "x", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("([^b]*)*", "b", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("([^b]*)*", "aaaaaab", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("([ab]*)*", "aaaabcde", &[Some((0, 5)), Some((0, 5))]);
+        test_posix("([^a]*)*", "aaaaaa", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("([^ab]*)*", "ccccxx", &[Some((0, 6)), Some((0, 6))]);
+        test_posix("([^ab]*)*", "ababab", &[Some((0, 0)), Some((0, 0))]);
+        test_posix("((z)+|a)*", "zabcde", &[Some((0, 2)), Some((1, 2)), None]); // The second iteration resets (z)
+        test_posix("(a*)*(x)", "x", &[Some((0, 1)), Some((0, 0)), Some((0, 1))]);
+        test_posix("(a*)*(x)", "ax", &[Some((0, 2)), Some((0, 1)), Some((1, 2))]);
+        test_posix("(a*)*(x)", "axa", &[Some((0, 2)), Some((0, 1)), Some((1, 2))]);
+        test_posix("(a*)+(x)", "x", &[Some((0, 1)), Some((0, 0)), Some((0, 1))]);
+        test_posix("(a*)+(x)", "ax", &[Some((0, 2)), Some((0, 1)), Some((1, 2))]);
+        test_posix("(a*){2}(x)", "x", &[Some((0, 1)), Some((0, 0)), Some((0, 1))]);
+        test_posix("(a*){2}(x)", "ax", &[Some((0, 2)), Some((1, 1)), Some((1, 2))]);
+        test_posix("(a*){2}(x)", "axa", &[Some((0, 2)), Some((1, 1)), Some((1, 2))]);
+        assert!(leftmost_longest("(a+)+").captures("x").is_none()); // No match
+    }
+
+    #[test]
+    fn test_posix_captures_on_long_haystack() {
+        // Repetitions are solved a whole iteration at a time, not by recursion
+        let haystack = "a".repeat(20_000);
+        test_posix("(a)*", &haystack, &[Some((0, 20_000)), Some((19_999, 20_000))]);
+
+        let haystack = "ab".repeat(10_000) + "c";
+        test_posix("(a|b)*c", &haystack, &[Some((0, 20_001)), Some((19_999, 20_000))]);
+
+        let haystack = "a".repeat(200);
+        test_posix("(a{1,20}){1,10}", &haystack, &[Some((0, 200)), Some((180, 200))]);
+    }
+
+    #[test]
+    fn test_leftmost_longest_rejects_backtracking_constructs() {
+        for pattern in [r"(a)\1", "a++", "(?>a)b", r"(?=(a)\1)"] {
+            let result = RRegexBuilder::new(pattern).match_kind(MatchKind::LeftmostLongest).build();
+            assert!(matches!(result, Err(ParseError::UnsupportedWithLeftmostLongest(_))), "{pattern}");
+        }
+    }
+}
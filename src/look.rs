@@ -0,0 +1,94 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::nfa::NFA;
+
+/// A look-around assertion over a sub-pattern compiled to its own NFA.
+#[derive(Debug, Clone)]
+pub(crate) struct Look {
+    pub(crate) id: usize,
+    pub(crate) nfa: Arc<NFA>,
+    pub(crate) ahead: bool,
+    pub(crate) negated: bool,
+}
+
+/// What a zero-width transition can see at a position in the input: the
+/// characters either side of it and which look-arounds hold there.
+pub(crate) struct Context<'a> {
+    pub(crate) prev: Option<char>,
+    pub(crate) next: Option<char>,
+    pub(crate) position: usize,
+    pub(crate) looks: &'a LookTables,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(input: &str, position: usize, looks: &'a LookTables) -> Self {
+        Context {
+            prev: input[..position].chars().next_back(),
+            next: input[position..].chars().next(),
+            position,
+            looks,
+        }
+    }
+}
+
+/// For every look-around in a pattern, whether its sub-pattern matches at
+/// each position of one input, so the automaton simulations can treat it as
+/// a plain assertion.
+///
+/// A lookahead holds where some match of its sub-pattern starts. Running the
+/// reversed sub-NFA from the end of the input, and restarting it at every
+/// position, finds all of those positions in one pass. Following the result
+/// alongside the main NFA amounts to intersecting it with the sub-pattern
+/// followed by anything. Lookbehind is the mirror image: the sub-NFA runs
+/// forwards and holds where some match ends, so it may be of any length.
+#[derive(Default)]
+pub(crate) struct LookTables {
+    tables: HashMap<usize, Vec<bool>>,
+}
+
+impl LookTables {
+    pub(crate) fn new(nfa: &NFA, input: &str) -> Self {
+        let mut looks = LookTables::default();
+
+        for look in nfa.looks() {
+            if looks.tables.contains_key(&look.id) { continue }
+
+            let table = match look.ahead {
+                true => looks.scan(&look.nfa.reversed(), input, true),
+                false => looks.scan(&look.nfa, input, false),
+            };
+            looks.tables.insert(look.id, table);
+        }
+
+        looks
+    }
+
+    pub(crate) fn holds(&self, look: &Look, position: usize) -> bool {
+        let matched = self.tables.get(&look.id).is_some_and(|table| table[position]);
+
+        matched != look.negated
+    }
+
+    // Runs `nfa` over the whole of `input`, forwards or backwards, adding its
+    // start state back at every position. Marks each byte offset at which the
+    // NFA accepts, that is, where some match ends in the direction of travel.
+    fn scan(&self, nfa: &NFA, input: &str, backwards: bool) -> Vec<bool> {
+        let mut table = vec![false; input.len() + 1];
+        let mut positions: Vec<usize> = input.char_indices().map(|(i, _)| i).chain([input.len()]).collect();
+        if backwards { positions.reverse(); }
+
+        let mut states = HashSet::new();
+
+        for (index, &position) in positions.iter().enumerate() {
+            states.insert(nfa.start_state);
+            states = nfa.epsilon_closure(states, &Context::new(input, position, self));
+            table[position] = nfa.is_accepting(&states);
+
+            let Some(&following) = positions.get(index + 1) else { break };
+            let ch = input[position.min(following)..].chars().next().expect("positions are char boundaries");
+            states = nfa.step(&states, ch);
+        }
+
+        table
+    }
+}
@@ -25,10 +25,12 @@ fn test_match(regex: &str, input: &str) -> Result<(), ParseError>{
 
     println!("Matching {} to input '{}'.", &regex, &input);
 
-    if rregex.matches(input) {
-        Ok(println!("Matches!"))
+    if rregex.is_full_match(input) {
+        println!("Matches!");
     }
     else {
-        Ok(println!("No Match."))
+        println!("No Match.");
     }
+
+    Ok(())
 }
\ No newline at end of file
@@ -1,58 +1,156 @@
 use std::collections::{HashSet, VecDeque};
+use crate::backtrack::Backtracker;
+use crate::builder::{Config, MatchKind};
+use crate::errors::MatchError;
+use crate::look::{Context, LookTables};
 use crate::nfa::{NFA, StateID, Transition};
+use crate::posix;
+
+/// Capture positions: slot `2 * i` holds the start of group `i` and slot
+/// `2 * i + 1` its end, as byte offsets.
+pub type Slots = Vec<Option<usize>>;
 
 pub struct Matcher {
-    nfa: NFA
+    nfa: NFA,
+    // Step budget for the backtracker, set only when the pattern has
+    // backreferences. Every other pattern runs on the automaton simulations.
+    backtrack_limit: Option<usize>,
+    match_kind: MatchKind,
 }
 
 impl Matcher {
     pub fn new(nfa: NFA) -> Self {
-        Matcher { nfa }
+        Matcher::with_config(nfa, &Config::default())
+    }
+
+    pub(crate) fn with_config(mut nfa: NFA, config: &Config) -> Self {
+        let backtrack_limit = nfa.has_backrefs().then_some(config.backtrack_limit);
+        if backtrack_limit.is_none() { nfa.lower_atomics(); }
+
+        Matcher { nfa, backtrack_limit, match_kind: config.match_kind }
+    }
+
+    /// Number of capture groups, including group 0 for the whole match.
+    pub fn group_count(&self) -> usize {
+        self.nfa.group_count
+    }
+
+    /// Whether the pattern matches anywhere in `input`, using the backtracker
+    /// when the pattern needs it and `search_simulation` otherwise.
+    pub fn try_is_match(&self, input: &str) -> Result<bool, MatchError> {
+        match self.backtrack_limit {
+            Some(limit) => Ok(Backtracker::new(&self.nfa, limit).captures_at(input, 0, 0, false)?.is_some()),
+            None => Ok(self.search_simulation(input)),
+        }
+    }
+
+    /// Whether the pattern matches the whole of `input`, using the backtracker
+    /// when the pattern needs it and `set_simulation` otherwise.
+    pub fn try_is_full_match(&self, input: &str) -> Result<bool, MatchError> {
+        match self.backtrack_limit {
+            Some(limit) => Ok(Backtracker::new(&self.nfa, limit).captures_at(input, 0, 0, true)?.is_some()),
+            None => Ok(self.set_simulation(input)),
+        }
+    }
+
+    /// As `find_at`, using the backtracker when the pattern needs it, or
+    /// `longest_at` for leftmost-longest matching.
+    pub fn try_find_at(&self, input: &str, start: usize) -> Result<Option<(usize, usize)>, MatchError> {
+        self.try_find_at_with(input, start, &self.look_tables(input))
+    }
+
+    /// As `captures_at`, using the backtracker when the pattern needs it, or
+    /// the POSIX submatch rules for leftmost-longest matching.
+    pub fn try_captures_at(&self, input: &str, start: usize, slot_count: usize) -> Result<Option<Slots>, MatchError> {
+        match (self.match_kind, self.backtrack_limit) {
+            (MatchKind::LeftmostLongest, _) => Ok(self.longest_captures_at(input, start, slot_count, &self.look_tables(input))),
+            (_, Some(limit)) => Backtracker::new(&self.nfa, limit).captures_at(input, start, slot_count, false),
+            (_, None) => Ok(self.captures_at_with(input, start, slot_count, &LookTables::new(&self.nfa, input))),
+        }
+    }
+
+    /// As `try_find_at`, with the look-around tables for `input` already
+    /// built by `look_tables`, so that searches over the same haystack can
+    /// share them.
+    pub(crate) fn try_find_at_with(&self, input: &str, start: usize, looks: &LookTables) -> Result<Option<(usize, usize)>, MatchError> {
+        let slots = match (self.match_kind, self.backtrack_limit) {
+            (MatchKind::LeftmostLongest, _) => return Ok(self.longest_at(input, start, looks)),
+            (_, Some(limit)) => Backtracker::new(&self.nfa, limit).captures_at(input, start, 2, false)?,
+            (_, None) => self.captures_at_with(input, start, 2, looks),
+        };
+
+        Ok(slots.and_then(|slots| slots[0].zip(slots[1])))
+    }
+
+    /// The look-around tables the automaton searches need for `input`. The
+    /// backtracker searches look-arounds itself, so it gets none.
+    pub(crate) fn look_tables(&self, input: &str) -> LookTables {
+        match self.backtrack_limit {
+            Some(_) => LookTables::default(),
+            None => LookTables::new(&self.nfa, input),
+        }
     }
 
     pub fn set_simulation(&self, input: &str) -> bool {
-        let mut current_states = self.epsilon_closure(
-            HashSet::from([self.nfa.start_state])
+        let looks = LookTables::new(&self.nfa, input);
+        let mut current_states = self.nfa.epsilon_closure(
+            HashSet::from([self.nfa.start_state]),
+            &Context::new(input, 0, &looks),
         );
 
-        for ch in input.chars() {
-            let mut next_states= HashSet::new();
+        for (position, ch) in input.char_indices() {
+            let next_states = self.nfa.step(&current_states, ch);
+            let context = Context::new(input, position + ch.len_utf8(), &looks);
 
-            for &state in &current_states {
-                if let Some(transitions) = self.nfa.transitions.get(&state) {
-                    for (transition, next_state) in transitions {
-                        match transition {
-                            Transition::Literal(c) if *c == ch => { next_states.insert(*next_state); },
-                            _ => {},
-                        }
-                    }
-                }
-            }
-
-            current_states = self.epsilon_closure(next_states);
+            current_states = self.nfa.epsilon_closure(next_states, &context);
 
             if current_states.is_empty() { return false }
         }
 
-        current_states.iter().any(|state| self.nfa.end_states.contains(state))
+        self.nfa.is_accepting(&current_states)
+    }
+
+    /// Unanchored variant of `set_simulation`: whether the NFA accepts any
+    /// substring of `input`. Rather than restarting at every offset, the start
+    /// state is added back into the active set at each position, so this is
+    /// one pass over the input.
+    pub fn search_simulation(&self, input: &str) -> bool {
+        let looks = LookTables::new(&self.nfa, input);
+        let mut position = 0;
+        let mut current_states = HashSet::new();
+
+        loop {
+            let context = Context::new(input, position, &looks);
+            current_states.insert(self.nfa.start_state);
+            current_states = self.nfa.epsilon_closure(current_states, &context);
+
+            if self.nfa.is_accepting(&current_states) { return true }
+
+            match context.next {
+                Some(ch) => {
+                    current_states = self.nfa.step(&current_states, ch);
+                    position += ch.len_utf8();
+                },
+                None => return false,
+            }
+        }
     }
 
     pub fn copy_simulation(&self, input: &str) -> bool {
+        let looks = LookTables::new(&self.nfa, input);
         let mut active_copies = VecDeque::new();
 
-        self.spawn_recursive_copies(self.nfa.start_state, &mut active_copies);
+        self.spawn_recursive_copies(self.nfa.start_state, &mut active_copies, &Context::new(input, 0, &looks));
 
-        for ch in input.chars() {
+        for (position, ch) in input.char_indices() {
+            let context = Context::new(input, position + ch.len_utf8(), &looks);
             let mut next_copies = VecDeque::new();
 
             while let Some(current_state) = active_copies.pop_front() {
                 if let Some(transitions) = self.nfa.transitions.get(&current_state) {
                     for (transition, next_state) in transitions {
-                        match transition {
-                            Transition::Literal(c) if *c == ch => {
-                                self.spawn_recursive_copies(*next_state, &mut next_copies);
-                            },
-                            _ => {},
+                        if transition.matches(ch) {
+                            self.spawn_recursive_copies(*next_state, &mut next_copies, &context);
                         }
                     }
                 }
@@ -66,33 +164,213 @@ impl Matcher {
         active_copies.iter().any(|state| self.nfa.end_states.contains(state))
     }
 
-    fn epsilon_closure(&self, states: HashSet<StateID>) -> HashSet<StateID> {
-        let mut closure = states.clone();
-        let mut stack = VecDeque::from_iter(states.iter());
+    /// Finds the leftmost-first match starting at or after byte offset `start`,
+    /// returning its byte range.
+    pub fn find_at(&self, input: &str, start: usize) -> Option<(usize, usize)> {
+        let slots = self.captures_at(input, start, 2)?;
+
+        Some((slots[0]?, slots[1]?))
+    }
+
+    /// Finds the leftmost-first match starting at or after byte offset `start`
+    /// and returns the first `slot_count` capture slots: the start and end of
+    /// group 0 (the whole match), then of group 1, and so on.
+    ///
+    /// This is a Pike VM. Threads are kept in priority order, each with its
+    /// own copy of the slots, and a new thread is seeded at every position
+    /// until some thread matches. Once one does, lower priority threads are
+    /// dropped and higher priority ones are run on in case they match later.
+    pub fn captures_at(&self, input: &str, start: usize, slot_count: usize) -> Option<Slots> {
+        self.captures_at_with(input, start, slot_count, &LookTables::new(&self.nfa, input))
+    }
+
+    fn captures_at_with(&self, input: &str, start: usize, slot_count: usize, looks: &LookTables) -> Option<Slots> {
+        let mut matched = None;
+        let mut position = start;
+        let mut current_threads = Vec::new();
+        let mut seen = HashSet::new();
+        let context = Context::new(input, start, looks);
+
+        self.add_thread(&mut current_threads, &mut seen, self.nfa.start_state, vec![None; slot_count], &context);
+
+        loop {
+            let ch = input[position..].chars().next();
+            let next_position = position + ch.map_or(0, char::len_utf8);
+            let context = Context::new(input, next_position, looks);
+            let mut next_threads = Vec::new();
+
+            seen.clear();
+
+            for (state, slots) in &current_threads {
+                if self.nfa.end_states.contains(state) {
+                    matched = Some(slots.clone());
+                    break
+                }
+
+                let (Some(ch), Some(transitions)) = (ch, self.nfa.transitions.get(state)) else { continue };
 
-        while let Some(state) = stack.pop_front() {
-            if let Some(transitions) = self.nfa.transitions.get(&state) {
                 for (transition, next_state) in transitions {
-                    if matches!(transition, Transition::Epsilon) && !closure.contains(next_state) {
-                        closure.insert(*next_state);
-                        stack.push_back(next_state);
+                    if transition.matches(ch) {
+                        self.add_thread(&mut next_threads, &mut seen, *next_state, slots.clone(), &context);
                     }
                 }
             }
+
+            if ch.is_none() { break }
+
+            if matched.is_none() {
+                let slots = vec![None; slot_count];
+                self.add_thread(&mut next_threads, &mut seen, self.nfa.start_state, slots, &context);
+            }
+
+            if next_threads.is_empty() { break }
+
+            current_threads = next_threads;
+            position = next_position;
+        }
+
+        matched
+    }
+
+    /// Finds the leftmost-longest match starting at or after byte offset
+    /// `start`: of the matches that start first, the one that ends last.
+    ///
+    /// This runs like `captures_at` with only group 0, but a thread that
+    /// matches doesn't stop the ones behind it. Threads are only dropped once
+    /// they started after the best match so far, and the search goes on until
+    /// none are left. Threads that started earlier come first on the list, so
+    /// when two reach the same state the one kept is the one that could give
+    /// the better match.
+    fn longest_at(&self, input: &str, start: usize, looks: &LookTables) -> Option<(usize, usize)> {
+        let mut longest: Option<(usize, usize)> = None;
+        let mut position = start;
+        let mut current_threads = Vec::new();
+        let mut seen = HashSet::new();
+        let context = Context::new(input, start, looks);
+
+        self.add_start_thread(&mut current_threads, &mut seen, self.nfa.start_state, start, &context);
+
+        loop {
+            let ch = input[position..].chars().next();
+            let next_position = position + ch.map_or(0, char::len_utf8);
+            let context = Context::new(input, next_position, looks);
+            let mut next_threads = Vec::new();
+
+            seen.clear();
+
+            for &(state, from) in &current_threads {
+                if self.nfa.end_states.contains(&state) {
+                    if longest.is_none_or(|(first, last)| from < first || from == first && position > last) {
+                        longest = Some((from, position));
+                    }
+                    continue
+                }
+
+                let (Some(ch), Some(transitions)) = (ch, self.nfa.transitions.get(&state)) else { continue };
+
+                for (transition, next_state) in transitions {
+                    if transition.matches(ch) {
+                        self.add_start_thread(&mut next_threads, &mut seen, *next_state, from, &context);
+                    }
+                }
+            }
+
+            if ch.is_none() { break }
+
+            match longest {
+                Some((first, _)) => next_threads.retain(|&(_, from)| from <= first),
+                None => self.add_start_thread(&mut next_threads, &mut seen, self.nfa.start_state, next_position, &context),
+            }
+
+            if next_threads.is_empty() { break }
+
+            current_threads = next_threads;
+            position = next_position;
+        }
+
+        longest
+    }
+
+    // The leftmost-longest match, with its groups set by the POSIX rules.
+    fn longest_captures_at(&self, input: &str, start: usize, slot_count: usize, looks: &LookTables) -> Option<Slots> {
+        let span = self.longest_at(input, start, looks)?;
+
+        posix::captures(&self.nfa.shape, input, span, looks, slot_count)
+    }
+
+    // Adds `state` and everything reachable from it through epsilon transitions
+    // to `threads`, depth first so that the list stays in priority order.
+    // States that are already on the list are left alone. Save transitions
+    // record the current position in the slots of the threads that pass
+    // through them.
+    fn add_thread(
+        &self,
+        threads: &mut Vec<(StateID, Slots)>,
+        seen: &mut HashSet<StateID>,
+        state: StateID,
+        slots: Slots,
+        context: &Context,
+    ) {
+        let mut stack = vec![(state, slots)];
+
+        while let Some((state, slots)) = stack.pop() {
+            if !seen.insert(state) { continue }
+
+            if let Some(transitions) = self.nfa.transitions.get(&state) {
+                for (transition, next_state) in transitions.iter().rev() {
+                    if !transition.epsilon_at(context) { continue }
+
+                    let mut slots = slots.clone();
+                    if let Transition::Save(slot) = transition && let Some(slot) = slots.get_mut(*slot) {
+                        *slot = Some(context.position);
+                    }
+                    stack.push((*next_state, slots));
+                }
+            }
+
+            threads.push((state, slots));
         }
+    }
+
+    // Like `add_thread` for threads that only need to know where they
+    // started, which saves cloning their slots at every step.
+    fn add_start_thread(
+        &self,
+        threads: &mut Vec<(StateID, usize)>,
+        seen: &mut HashSet<StateID>,
+        state: StateID,
+        start: usize,
+        context: &Context,
+    ) {
+        let mut stack = vec![state];
 
-        closure
+        while let Some(state) = stack.pop() {
+            if !seen.insert(state) { continue }
+
+            if let Some(transitions) = self.nfa.transitions.get(&state) {
+                for (transition, next_state) in transitions.iter().rev() {
+                    if transition.epsilon_at(context) { stack.push(*next_state) }
+                }
+            }
+
+            threads.push((state, start));
+        }
     }
 
-    fn spawn_recursive_copies(&self, state: StateID, copies: &mut VecDeque<StateID>) {
+    fn spawn_recursive_copies(
+        &self,
+        state: StateID,
+        copies: &mut VecDeque<StateID>,
+        context: &Context,
+    ) {
         if copies.contains(&state) { return ;}
 
         copies.push_back(state);
 
         if let Some(transitions) = self.nfa.transitions.get(&state) {
             for (transition, next_state) in transitions {
-                if matches!(transition, Transition::Epsilon) {
-                    self.spawn_recursive_copies(*next_state, copies);
+                if transition.epsilon_at(context) {
+                    self.spawn_recursive_copies(*next_state, copies, context);
                 }
             }
         }
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::Matcher;
+use crate::look::LookTables;
+use crate::matcher::Slots;
+
+/// A single match of a pattern in a haystack, as a byte range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    pub(crate) fn new(haystack: &'h str, start: usize, end: usize) -> Self {
+        Match { haystack, start, end }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+}
+
+/// Iterator over successive non-overlapping matches, created by `RRegex::find_iter`.
+pub struct Matches<'r, 'h> {
+    matcher: &'r Matcher,
+    haystack: &'h str,
+    position: usize,
+    last_end: Option<usize>,
+    // Built once for the haystack rather than for every search
+    looks: LookTables,
+}
+
+impl<'r, 'h> Matches<'r, 'h> {
+    pub(crate) fn new(matcher: &'r Matcher, haystack: &'h str) -> Self {
+        let looks = matcher.look_tables(haystack);
+
+        Matches { matcher, haystack, position: 0, last_end: None, looks }
+    }
+
+    // Panics if the backtracking budget runs out, as `RRegex::find` does.
+    fn find_at(&self, start: usize) -> Option<(usize, usize)> {
+        self.matcher.try_find_at_with(self.haystack, start, &self.looks).unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        if self.position > self.haystack.len() { return None }
+
+        let (mut start, mut end) = self.find_at(self.position)?;
+
+        // An empty match right where the previous match ended is skipped, as in
+        // the `regex` crate. Searching again one character further on also
+        // guarantees progress, and never splits a multi-byte character.
+        if start == end && Some(end) == self.last_end {
+            let Some(ch) = self.haystack[end..].chars().next() else {
+                self.position = self.haystack.len() + 1;
+                return None
+            };
+            (start, end) = self.find_at(end + ch.len_utf8())?;
+        }
+
+        self.position = end;
+        self.last_end = Some(end);
+
+        Some(Match::new(self.haystack, start, end))
+    }
+}
+
+/// The spans of every capture group in one match, created by `RRegex::captures`.
+/// Group 0 is the whole match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    slots: Slots,
+    names: Arc<HashMap<String, usize>>,
+}
+
+impl<'h> Captures<'h> {
+    pub(crate) fn new(haystack: &'h str, slots: Slots, names: Arc<HashMap<String, usize>>) -> Self {
+        Captures { haystack, slots, names }
+    }
+
+    /// The match for group `index`, or `None` if that group did not take part
+    /// in the match or doesn't exist.
+    pub fn get(&self, index: usize) -> Option<Match<'h>> {
+        let start = (*self.slots.get(2 * index)?)?;
+        let end = (*self.slots.get(2 * index + 1)?)?;
+
+        Some(Match::new(self.haystack, start, end))
+    }
+
+    /// The match for the group called `name`, or `None` if that group did not
+    /// take part in the match or doesn't exist.
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        self.get(*self.names.get(name)?)
+    }
+
+    /// Number of groups in the pattern, including group 0.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+}
@@ -1,5 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use crate::class::CharClass;
+use crate::look::{Context, Look};
+use crate::posix::Shape;
+use crate::unicode;
 
 static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 pub(crate) type StateID = usize;
@@ -8,10 +13,94 @@ fn next_state_id() -> usize {
     NEXT_ID.fetch_add(1, Ordering::SeqCst)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum Transition {
     Epsilon,
     Literal(char),
+    Class(CharClass),
+    Any { newline: bool },
+    Assert(Assertion),
+    Save(usize),
+    Backref { group: usize, case_insensitive: bool },
+    Look(Look),
+    Atomic(Atomic),
+}
+
+/// An atomic group: once its sub-pattern has matched, the first way it found
+/// is kept and never backtracked into. `plain` is an equivalent NFA without
+/// that rule, for the automaton simulations, when one is known.
+#[derive(Debug, Clone)]
+pub(crate) struct Atomic {
+    pub(crate) nfa: Arc<NFA>,
+    pub(crate) plain: Option<Arc<NFA>>,
+}
+
+/// A zero-width condition on the characters either side of the current position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assertion {
+    StartText,
+    EndText,
+    StartLine,
+    EndLine,
+    WordBoundary { unicode: bool },
+    NotWordBoundary { unicode: bool },
+    WordStart { unicode: bool },
+    WordEnd { unicode: bool },
+}
+
+impl Assertion {
+    pub(crate) fn holds(&self, prev: Option<char>, next: Option<char>) -> bool {
+        match self {
+            Assertion::StartText => prev.is_none(),
+            Assertion::EndText => next.is_none(),
+            Assertion::StartLine => prev.is_none_or(|c| c == '\n'),
+            Assertion::EndLine => next.is_none_or(|c| c == '\n'),
+            Assertion::WordBoundary { unicode } => {
+                is_word(prev, *unicode) != is_word(next, *unicode)
+            },
+            Assertion::NotWordBoundary { unicode } => {
+                is_word(prev, *unicode) == is_word(next, *unicode)
+            },
+            Assertion::WordStart { unicode } => {
+                !is_word(prev, *unicode) && is_word(next, *unicode)
+            },
+            Assertion::WordEnd { unicode } => {
+                is_word(prev, *unicode) && !is_word(next, *unicode)
+            },
+        }
+    }
+}
+
+fn is_word(c: Option<char>, unicode: bool) -> bool {
+    c.is_some_and(|c| unicode::is_word_character(c, unicode))
+}
+
+impl Transition {
+    /// Whether this transition consumes `ch`. Epsilon transitions never do.
+    pub(crate) fn matches(&self, ch: char) -> bool {
+        match self {
+            Transition::Epsilon |
+            Transition::Assert(_) |
+            Transition::Save(_) |
+            Transition::Backref { .. } |
+            Transition::Look(_) |
+            Transition::Atomic(_) => false,
+            Transition::Literal(c) => *c == ch,
+            Transition::Class(class) => class.contains(ch),
+            Transition::Any { newline } => *newline || ch != '\n',
+        }
+    }
+
+    /// Whether this transition can be taken without consuming input at the
+    /// position described by `context`.
+    pub(crate) fn epsilon_at(&self, context: &Context) -> bool {
+        match self {
+            Transition::Epsilon | Transition::Save(_) => true,
+            Transition::Assert(assertion) => assertion.holds(context.prev, context.next),
+            Transition::Look(look) => context.looks.holds(look, context.position),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -19,6 +108,16 @@ pub struct NFA {
     pub(crate) start_state: StateID,
     pub(crate) end_states: Vec<StateID>,
     pub(crate) transitions: HashMap<StateID, Vec<(Transition, StateID)>>,
+    /// Number of capture groups including group 0. Only set on the NFA for a
+    /// whole pattern, by the parser.
+    pub(crate) group_count: usize,
+    pub(crate) shape: Shape,
+}
+
+impl Default for NFA {
+    fn default() -> Self {
+        NFA::new()
+    }
 }
 
 impl NFA {
@@ -27,6 +126,8 @@ impl NFA {
             start_state: next_state_id(),
             end_states: Vec::new(),
             transitions: HashMap::new(),
+            group_count: 0,
+            shape: Shape::Empty,
         }
     }
 
@@ -38,10 +139,22 @@ impl NFA {
     ){
         self.transitions
             .entry(from)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push((transition, to));
     }
 
+    // Adds epsilon edges from `from` into a repeated NFA and past it. The
+    // first edge has priority, so a greedy branch tries `enter` first.
+    fn add_branch(&mut self, from: StateID, enter: StateID, exit: StateID, greedy: bool) {
+        let (first, second) = match greedy {
+            true => (enter, exit),
+            false => (exit, enter),
+        };
+
+        self.add_transition(from, Transition::Epsilon, first);
+        self.add_transition(from, Transition::Epsilon, second);
+    }
+
     pub fn literal(c: char) -> Self {
         let mut nfa = NFA::new();
         let start = next_state_id();
@@ -53,10 +166,332 @@ impl NFA {
             end);
         nfa.start_state = start;
         nfa.end_states.push(end);
+        nfa.shape = Shape::Char(Transition::Literal(c));
+
+        nfa
+    }
+
+    pub fn class(class: CharClass) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_transition(
+            start,
+            Transition::Class(class.clone()),
+            end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Char(Transition::Class(class));
+
+        nfa
+    }
+
+    pub fn any(newline: bool) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_transition(
+            start,
+            Transition::Any { newline },
+            end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Char(Transition::Any { newline });
+
+        nfa
+    }
+
+    pub fn assertion(assertion: Assertion) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_transition(
+            start,
+            Transition::Assert(assertion),
+            end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Zero(Transition::Assert(assertion));
+
+        nfa
+    }
+
+    /// Wraps `nfa1` in Save transitions recording where group `index` starts and ends.
+    pub fn capture(nfa1: Self, index: usize) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_transition(start, Transition::Save(2 * index), nfa1.start_state);
+
+        for &end_state in &nfa1.end_states {
+            nfa.add_transition(end_state, Transition::Save(2 * index + 1), end);
+        }
+
+        nfa.transitions.extend(nfa1.transitions);
+
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Group { index, shape: Box::new(nfa1.shape) };
+
+        nfa
+    }
+
+    /// Matches the text last captured by group `group` again.
+    pub fn backref(group: usize, case_insensitive: bool) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_transition(start, Transition::Backref { group, case_insensitive }, end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Unsupported;
+
+        nfa
+    }
+
+    /// A look-around assertion: whether `nfa1` matches just after the current
+    /// position (lookahead) or just before it (lookbehind), or with `negated`
+    /// set, that it doesn't.
+    pub fn look(nfa1: Self, ahead: bool, negated: bool) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        let look = Look {
+            id: next_state_id(),
+            nfa: Arc::new(nfa1),
+            ahead,
+            negated,
+        };
+
+        nfa.shape = Shape::Zero(Transition::Look(look.clone()));
+        nfa.add_transition(start, Transition::Look(look), end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+
+        nfa
+    }
+
+    /// An atomic group around `nfa1`. `plain` is an NFA that matches the same
+    /// way for the automaton simulations; when `nfa1` always matches the same
+    /// number of characters it is `nfa1` itself, as there's nothing another
+    /// way of matching it could change.
+    pub fn atomic(nfa1: Self, plain: Option<Self>) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        let plain = plain.or_else(|| nfa1.fixed_length().map(|_| nfa1.duplicate()));
+        let atomic = Atomic {
+            nfa: Arc::new(nfa1),
+            plain: plain.map(Arc::new),
+        };
+
+        nfa.add_transition(start, Transition::Atomic(atomic), end);
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Unsupported;
 
         nfa
     }
 
+    /// Whether any transition, including those inside look-arounds and atomic
+    /// groups, is a backreference, which only the backtracker can follow.
+    pub(crate) fn has_backrefs(&self) -> bool {
+        self.transitions
+            .values()
+            .flatten()
+            .any(|(transition, _)| match transition {
+                Transition::Backref { .. } => true,
+                Transition::Look(look) => look.nfa.has_backrefs(),
+                Transition::Atomic(atomic) => atomic.nfa.has_backrefs(),
+                _ => false,
+            })
+    }
+
+    /// Whether some atomic group has no equivalent the automaton simulations
+    /// can run.
+    pub(crate) fn has_unlowerable_atomics(&self) -> bool {
+        self.transitions
+            .values()
+            .flatten()
+            .any(|(transition, _)| match transition {
+                Transition::Look(look) => look.nfa.has_unlowerable_atomics(),
+                Transition::Atomic(atomic) => atomic.plain.as_ref().is_none_or(|plain| plain.has_unlowerable_atomics()),
+                _ => false,
+            })
+    }
+
+    /// Replaces every atomic group, including those inside look-arounds, with
+    /// a copy of its plain equivalent, for the automaton simulations. Groups
+    /// without one are left as they are and never match.
+    pub(crate) fn lower_atomics(&mut self) {
+        let mut spliced = Vec::new();
+
+        for (transition, to) in self.transitions.values_mut().flatten() {
+            match transition {
+                Transition::Look(look) if look.nfa.has_atomics() => {
+                    let mut nfa = look.nfa.duplicate();
+                    nfa.lower_atomics();
+                    look.nfa = Arc::new(nfa);
+                },
+                Transition::Atomic(Atomic { plain: Some(plain), .. }) => {
+                    let mut nfa = plain.duplicate();
+                    nfa.lower_atomics();
+                    *transition = Transition::Epsilon;
+                    spliced.push((std::mem::replace(to, nfa.start_state), nfa));
+                },
+                _ => {},
+            }
+        }
+
+        for (to, nfa) in spliced {
+            for &end_state in &nfa.end_states {
+                self.add_transition(end_state, Transition::Epsilon, to);
+            }
+            self.transitions.extend(nfa.transitions);
+        }
+    }
+
+    pub(crate) fn has_atomics(&self) -> bool {
+        self.transitions
+            .values()
+            .flatten()
+            .any(|(transition, _)| match transition {
+                Transition::Look(look) => look.nfa.has_atomics(),
+                Transition::Atomic(_) => true,
+                _ => false,
+            })
+    }
+
+    /// The number of characters every match consumes, if that is the same for
+    /// all of them.
+    pub(crate) fn fixed_length(&self) -> Option<usize> {
+        let mut lengths = HashMap::from([(self.start_state, 0)]);
+        let mut stack = vec![self.start_state];
+
+        while let Some(state) = stack.pop() {
+            let length = lengths[&state];
+
+            for (transition, to) in self.transitions.get(&state).into_iter().flatten() {
+                let step = match transition {
+                    Transition::Epsilon | Transition::Save(_) | Transition::Assert(_) | Transition::Look(_) => 0,
+                    Transition::Literal(_) | Transition::Class(_) | Transition::Any { .. } => 1,
+                    Transition::Atomic(atomic) => atomic.nfa.fixed_length()?,
+                    Transition::Backref { .. } => return None,
+                };
+
+                match lengths.get(to) {
+                    Some(&other) if other != length + step => return None,
+                    Some(_) => {},
+                    None => {
+                        lengths.insert(*to, length + step);
+                        stack.push(*to);
+                    },
+                }
+            }
+        }
+
+        let mut ends = self.end_states.iter().filter_map(|state| lengths.get(state));
+        let first = *ends.next()?;
+
+        ends.all(|&length| length == first).then_some(first)
+    }
+
+    /// The characters this NFA matches, if it is a single literal, class or
+    /// dot.
+    pub(crate) fn single_class(&self) -> Option<CharClass> {
+        let [(transition, to)] = self.transitions.get(&self.start_state)?.as_slice() else { return None };
+        if self.transitions.len() != 1 || self.end_states != [*to] { return None }
+
+        match transition {
+            &Transition::Literal(c) => Some(CharClass::new(vec![(c, c)])),
+            Transition::Class(class) => Some(class.clone()),
+            Transition::Any { newline: true } => Some(CharClass::new(vec![('\0', char::MAX)])),
+            Transition::Any { newline: false } => Some(CharClass::new(vec![('\n', '\n')]).negate()),
+            _ => None,
+        }
+    }
+
+    /// The look-arounds used by this NFA, innermost first, so that each one
+    /// comes after those its own sub-NFA depends on.
+    pub(crate) fn looks(&self) -> Vec<&Look> {
+        let mut looks = Vec::new();
+
+        for (transition, _) in self.transitions.values().flatten() {
+            if let Transition::Look(look) = transition {
+                looks.extend(look.nfa.looks());
+                looks.push(look);
+            }
+        }
+
+        looks
+    }
+
+    /// This NFA with every transition turned around, so it accepts the
+    /// reverse of each string the original accepts.
+    pub(crate) fn reversed(&self) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+
+        for &end_state in &self.end_states {
+            nfa.add_transition(start, Transition::Epsilon, end_state);
+        }
+
+        for (&from, transitions) in &self.transitions {
+            for (transition, to) in transitions {
+                nfa.add_transition(*to, transition.clone(), from);
+            }
+        }
+
+        nfa.start_state = start;
+        nfa.end_states.push(self.start_state);
+
+        nfa
+    }
+
+    /// The states reached from `states` by consuming `ch`.
+    pub(crate) fn step(&self, states: &HashSet<StateID>, ch: char) -> HashSet<StateID> {
+        let mut next_states = HashSet::new();
+
+        for state in states {
+            if let Some(transitions) = self.transitions.get(state) {
+                for (transition, next_state) in transitions {
+                    if transition.matches(ch) { next_states.insert(*next_state); }
+                }
+            }
+        }
+
+        next_states
+    }
+
+    /// Follows epsilon transitions, and assertions that hold at `context`.
+    pub(crate) fn epsilon_closure(&self, states: HashSet<StateID>, context: &Context) -> HashSet<StateID> {
+        let mut closure = states.clone();
+        let mut stack = Vec::from_iter(states);
+
+        while let Some(state) = stack.pop() {
+            if let Some(transitions) = self.transitions.get(&state) {
+                for (transition, next_state) in transitions {
+                    if transition.epsilon_at(context) && closure.insert(*next_state) {
+                        stack.push(*next_state);
+                    }
+                }
+            }
+        }
+
+        closure
+    }
+
+    pub(crate) fn is_accepting(&self, states: &HashSet<StateID>) -> bool {
+        states.iter().any(|state| self.end_states.contains(state))
+    }
+
     pub fn epsilon() -> Self {
         let mut nfa = NFA::new();
         let start = next_state_id();
@@ -111,6 +546,7 @@ impl NFA {
 
         nfa.start_state = start;
         nfa.end_states.push(end);
+        nfa.shape = Shape::alternate(nfa1.shape, nfa2.shape);
 
         nfa
     }
@@ -133,20 +569,64 @@ impl NFA {
 
         nfa.start_state = start;
         nfa.end_states = end;
+        nfa.shape = Shape::concat(nfa1.shape, nfa2.shape);
 
         nfa
     }
 
-    pub fn kleene_star(nfa1: Self) -> Self {
+    /// Zero or more repetitions. A greedy star prefers another iteration over
+    /// leaving, a lazy one prefers leaving.
+    pub fn kleene_star(nfa1: Self, greedy: bool) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_branch(start, nfa1.start_state, end, greedy);
+
+        for &end_state in &nfa1.end_states {
+            nfa.add_branch(end_state, nfa1.start_state, end, greedy);
+        }
+
+        nfa.transitions.extend(nfa1.transitions);
+
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Repeat { shape: Box::new(nfa1.shape), min: 0, max: None };
+
+        nfa
+    }
+
+    /// One or more repetitions, greedy or lazy as for `kleene_star`.
+    pub fn kleene_plus(nfa1: Self, greedy: bool) -> Self {
         let mut nfa = NFA::new();
         let start = next_state_id();
         let end = next_state_id();
 
         nfa.add_transition(start, Transition::Epsilon, nfa1.start_state);
-        nfa.add_transition(start, Transition::Epsilon, end);
 
         for &end_state in &nfa1.end_states {
-            nfa.add_transition(end_state, Transition::Epsilon, nfa1.start_state);
+            nfa.add_branch(end_state, nfa1.start_state, end, greedy);
+        }
+
+        nfa.transitions.extend(nfa1.transitions);
+
+        nfa.start_state = start;
+        nfa.end_states.push(end);
+        nfa.shape = Shape::Repeat { shape: Box::new(nfa1.shape), min: 1, max: None };
+
+        nfa
+    }
+
+    /// Zero or one occurrence. A greedy optional prefers matching, a lazy one
+    /// prefers skipping.
+    pub fn optional(nfa1: Self, greedy: bool) -> Self {
+        let mut nfa = NFA::new();
+        let start = next_state_id();
+        let end = next_state_id();
+
+        nfa.add_branch(start, nfa1.start_state, end, greedy);
+
+        for &end_state in &nfa1.end_states {
             nfa.add_transition(end_state, Transition::Epsilon, end);
         }
 
@@ -154,7 +634,77 @@ impl NFA {
 
         nfa.start_state = start;
         nfa.end_states.push(end);
+        nfa.shape = Shape::Repeat { shape: Box::new(nfa1.shape), min: 0, max: Some(1) };
+
+        nfa
+    }
+
+    pub fn repetition(nfa1: Self, min: usize, max: Option<usize>, greedy: bool) -> Self {
+        let shape = Shape::Repeat { shape: Box::new(nfa1.shape.clone()), min, max };
+        let mut nfa = match min {
+            0 => NFA::epsilon(),
+            _ => (1..min).fold(nfa1.duplicate(), |acc, _| NFA::concatenate(acc, nfa1.duplicate())),
+        };
 
+        match max {
+            None => nfa = NFA::concatenate(nfa, NFA::kleene_star(nfa1, greedy)),
+            Some(max) if max > min => {
+                // x{n,m} becomes x{n}(x(x(...)?)?)? so each optional copy is only tried after the previous one matched.
+                let mut optional = NFA::optional(nfa1.duplicate(), greedy);
+
+                for _ in min + 1..max {
+                    optional = NFA::optional(NFA::concatenate(nfa1.duplicate(), optional), greedy);
+                }
+
+                nfa = NFA::concatenate(nfa, optional);
+            },
+            Some(_) => {},
+        }
+
+        nfa.shape = shape;
         nfa
     }
-}
\ No newline at end of file
+
+    /// Copies this NFA, giving every state a fresh id so the copy can be combined with the original.
+    pub fn duplicate(&self) -> Self {
+        let mut ids = HashMap::new();
+        let mut rename = |state: StateID| *ids.entry(state).or_insert_with(next_state_id);
+
+        let mut nfa = NFA::new();
+        nfa.start_state = rename(self.start_state);
+        nfa.end_states = self.end_states.iter().map(|&state| rename(state)).collect();
+        nfa.shape = self.shape.clone();
+
+        for (&from, transitions) in &self.transitions {
+            for (transition, to) in transitions {
+                let from = rename(from);
+                let to = rename(*to);
+                nfa.add_transition(from, transition.clone(), to);
+            }
+        }
+
+        nfa
+    }
+
+    pub fn state_count(&self) -> usize {
+        let mut states = HashSet::from([self.start_state]);
+
+        states.extend(&self.end_states);
+
+        let mut nested = 0;
+
+        for (&from, transitions) in &self.transitions {
+            states.insert(from);
+            for (transition, to) in transitions {
+                states.insert(*to);
+                match transition {
+                    Transition::Look(look) => nested += look.nfa.state_count(),
+                    Transition::Atomic(atomic) => nested += atomic.nfa.state_count(),
+                    _ => {},
+                }
+            }
+        }
+
+        states.len() + nested
+    }
+}
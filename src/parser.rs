@@ -1,54 +1,95 @@
 use crate::{
     Lexer,
-    token::Token,
-    errors::ParseError
+    token::{Token, Flags},
+    errors::ParseError,
+    builder::{Config, MatchKind},
+    class::CharClass,
 };
-use crate::nfa::NFA;
+use crate::nfa::{NFA, Assertion};
 
 // Parser v2 lets see how this goes
 // This time we are trying to translate the following grammar into code:
 // Regex → Alternation
 // Alternation → Concatenation ('|' Concatenation) *
 // Concatenation → Term+
-// Term → Factor Postfix?
-//  Factor → Literal | '(' Regex ')' | ε
-//  Postfix → '*' | '+' | '?'
+// Term → Factor Postfix*
+//  Factor → Literal | Class | '.' | Anchor | Backref | Group | '(?flags)' | ε
+//  Group → ('(' | '(?<name>' | '(?flags:' | '(?=' | '(?!' | '(?<=' | '(?<!' | '(?>') Regex ')'
+//  Postfix → ('*' | '+' | '?' | '{' n (',' m?)? '}') ('?' | '+')?
 
 #[derive(Debug)]
 pub struct Parser {
-    tokens: Vec<Token>
+    tokens: Vec<Token>,
+    config: Config,
+    group_names: Vec<Option<String>>,
 }
 
 impl Parser {
-    pub fn new(lexer: &mut Lexer) -> Self {
-        let tokens = match lexer.collect().expect("Unexpected None when Parsing") {
-            Ok(vec) => vec,
-            Err(e) => panic!("{e}"),
-        };
+    pub fn new(lexer: &mut Lexer) -> Result<Self, ParseError> {
+        Parser::with_config(lexer, Config::default())
+    }
 
-        Parser {
-            tokens
-        }
+    pub(crate) fn with_config(lexer: &mut Lexer, config: Config) -> Result<Self, ParseError> {
+        let tokens = lexer.collect().expect("Unexpected None when Parsing")?;
+
+        Ok(Parser {
+            tokens,
+            config,
+            group_names: vec![None],
+        })
     }
 
     pub fn parse(&mut self) -> Result<NFA, ParseError> {
-        self.parse_alternation()
+        let mut nfa = NFA::capture(self.parse_alternation(&Flags::default())?, 0);
+
+        // Only an unopened ')' can stop the top-level alternation early
+        if self.peek().is_some() { return Err(ParseError::MismatchedParentheses) }
+
+        self.check_size(nfa.state_count())?;
+        nfa.group_count = self.group_names.len();
+
+        // The POSIX rules say nothing about constructs that depend on the
+        // order in which a backtracker tries things.
+        if self.config.match_kind == MatchKind::LeftmostLongest {
+            if nfa.has_backrefs() { return Err(ParseError::UnsupportedWithLeftmostLongest("Backreferences")) }
+            if nfa.has_atomics() { return Err(ParseError::UnsupportedWithLeftmostLongest("Atomic groups and possessive quantifiers")) }
+        }
+
+        // Only the backtracker can run an atomic group that has no plain
+        // equivalent, and it is only used for patterns with backreferences.
+        if nfa.has_unlowerable_atomics() && !nfa.has_backrefs() {
+            return Err(ParseError::UnsupportedAtomicGroup)
+        }
+
+        Ok(nfa)
     }
 
-    fn parse_alternation(&mut self) -> Result<NFA, ParseError> {
+    // Names of the capture groups in index order, with None for unnamed
+    // groups. Group 0, the whole match, is always unnamed.
+    pub fn group_names(&self) -> &[Option<String>] {
+        &self.group_names
+    }
+
+    // Parses an alternation with `flags` applied on top of the enclosing
+    // configuration. Flags set inside, by the group opener or by `(?flags)`,
+    // end with the alternation.
+    fn parse_alternation(&mut self, flags: &Flags) -> Result<NFA, ParseError> {
+        let outer = self.config.clone();
+        self.config.apply(flags);
+
         let mut nfa = self.parse_concatenation()?;
 
         while self.consume_if(Token::Union) {
             let rhs = match self.peek() {
-                Some(Token::Literal(_)) | 
-                Some(Token::LParen) | 
-                Some(Token::Union) => self.parse_concatenation()?,
-                Some(_) |
-                None => NFA::epsilon()
-            }; 
+                Some(Token::RParen) |
+                None => NFA::epsilon(),
+                Some(_) => self.parse_concatenation()?,
+            };
             nfa = NFA::union(nfa, rhs);
         }
 
+        self.config = outer;
+
         Ok(nfa)
     }
 
@@ -67,12 +108,31 @@ impl Parser {
     fn parse_term(&mut self) -> Result<NFA, ParseError> {
         let mut nfa = self.parse_factor()?;
 
-        if let Some(postfix) = self.peek_postfix() {
+        // `*`, `?` and `{n,m}` stack, each applying to the repetition before
+        // it, so `a{2}{3}` is six a's and `a**` is `(?:a*)*`. A `?` right after
+        // a quantifier makes it lazy instead, and a `+` makes it possessive,
+        // so `a*+`, `a{2}+` and `a++` never stack
+        while let Some(postfix) = self.peek_postfix() {
             self.consume(); //Consume postfix
+            let greedy = !self.consume_if(Token::Question); //A trailing '?' makes it lazy
+            let possessive = greedy && self.consume_if(Token::Plus); //A trailing '+' makes it possessive
+            let operand = nfa.single_class().filter(|_| possessive);
+
             match postfix {
-                Token::Star => nfa = NFA::kleene_star(nfa),
+                Token::Star => nfa = NFA::kleene_star(nfa, greedy),
+                Token::Plus => nfa = NFA::kleene_plus(nfa, greedy),
+                Token::Question => nfa = NFA::optional(nfa, greedy),
+                Token::Repeat { min, max } => {
+                    let copies = max.unwrap_or(min).max(min.saturating_add(1));
+                    self.check_size(nfa.state_count().saturating_mul(copies))?;
+                    nfa = NFA::repetition(nfa, min, max, greedy)
+                },
                 _ => unreachable!()
             }
+
+            if possessive {
+                nfa = NFA::atomic(nfa, operand.and_then(|class| possessive_equivalent(class, &postfix)));
+            }
         }
 
         Ok(nfa)
@@ -80,13 +140,15 @@ impl Parser {
 
     fn parse_factor(&mut self) -> Result<NFA, ParseError> {
         match self.peek() {
-            Some(Token::LParen) => {
-                self.consume_if(Token::LParen); //Consume LParen
-                let nfa = self.parse_alternation()?;
-                if !self.consume_if(Token::RParen) {
-                    return Err(ParseError::MismatchedParentheses)
-                }
-                Ok(nfa)
+            Some(Token::LParen |
+                 Token::NamedGroup(_) |
+                 Token::NonCapturing(_) |
+                 Token::Look { .. } |
+                 Token::Atomic) => self.parse_group(),
+            Some(&Token::SetFlags(flags)) => {
+                self.consume(); //Consume flags
+                self.config.apply(&flags);
+                Ok(NFA::epsilon())
             },
             Some(Token::Literal(c)) => {
                 let c = *c;
@@ -94,15 +156,119 @@ impl Parser {
                 let nfa = NFA::literal(c);
                 Ok(nfa)
             },
+            Some(Token::Dot) => {
+                self.consume(); //Consume dot
+                Ok(NFA::any(self.config.dot_matches_new_line))
+            },
+            Some(Token::Caret) => {
+                self.consume(); //Consume caret
+                match self.config.multi_line {
+                    true => Ok(NFA::assertion(Assertion::StartLine)),
+                    false => Ok(NFA::assertion(Assertion::StartText)),
+                }
+            },
+            Some(Token::Dollar) => {
+                self.consume(); //Consume dollar
+                match self.config.multi_line {
+                    true => Ok(NFA::assertion(Assertion::EndLine)),
+                    false => Ok(NFA::assertion(Assertion::EndText)),
+                }
+            },
+            Some(Token::StartText) => {
+                self.consume(); //Consume \A
+                Ok(NFA::assertion(Assertion::StartText))
+            },
+            Some(Token::EndText) => {
+                self.consume(); //Consume \z
+                Ok(NFA::assertion(Assertion::EndText))
+            },
+            Some(Token::WordBoundary) => {
+                self.consume(); //Consume \b
+                Ok(NFA::assertion(Assertion::WordBoundary { unicode: self.config.unicode }))
+            },
+            Some(Token::NotWordBoundary) => {
+                self.consume(); //Consume \B
+                Ok(NFA::assertion(Assertion::NotWordBoundary { unicode: self.config.unicode }))
+            },
+            Some(Token::WordStart) => {
+                self.consume(); //Consume \<
+                Ok(NFA::assertion(Assertion::WordStart { unicode: self.config.unicode }))
+            },
+            Some(Token::WordEnd) => {
+                self.consume(); //Consume \>
+                Ok(NFA::assertion(Assertion::WordEnd { unicode: self.config.unicode }))
+            },
+            Some(&Token::Backref(group)) => {
+                self.consume(); //Consume \N
+                if group >= self.group_names.len() {
+                    return Err(ParseError::InvalidBackreference(group))
+                }
+                Ok(NFA::backref(group, self.config.case_insensitive))
+            },
+            Some(Token::NamedBackref(name)) => {
+                let name = name.clone();
+                self.consume(); //Consume \k<name>
+                let group = self.group_names
+                    .iter()
+                    .position(|n| n.as_deref() == Some(name.as_str()))
+                    .ok_or(ParseError::UnknownGroupName(name))?;
+                Ok(NFA::backref(group, self.config.case_insensitive))
+            },
+            Some(Token::Class(class)) => {
+                let class = class.clone();
+                self.consume(); //Consume class
+                Ok(NFA::class(class))
+            },
             Some(Token::Union) |
             Some(Token::RParen) |
             None => {
                 Ok(NFA::epsilon())
             }
-            Some(t) => Err(ParseError::UnexpectedToken(*t))
+            Some(t) => Err(ParseError::UnexpectedToken(t.clone()))
         }
     }
 
+    fn parse_group(&mut self) -> Result<NFA, ParseError> {
+        let opener = self.peek().cloned();
+
+        let (index, flags) = match &opener {
+            Some(Token::NamedGroup(name)) => {
+                if self.group_names.contains(&Some(name.clone())) {
+                    return Err(ParseError::DuplicateGroupName(name.clone()))
+                }
+                self.group_names.push(Some(name.clone()));
+                (Some(self.group_names.len() - 1), Flags::default())
+            },
+            Some(Token::LParen) => {
+                self.group_names.push(None);
+                (Some(self.group_names.len() - 1), Flags::default())
+            },
+            Some(Token::NonCapturing(flags)) => (None, *flags),
+            _ => (None, Flags::default()),
+        };
+        self.consume(); //Consume group opener
+
+        let nfa = self.parse_alternation(&flags)?;
+        if !self.consume_if(Token::RParen) {
+            return Err(ParseError::MismatchedParentheses)
+        }
+
+        match (opener, index) {
+            (Some(Token::Look { ahead, negated }), _) => Ok(NFA::look(nfa, ahead, negated)),
+            (Some(Token::Atomic), _) => Ok(NFA::atomic(nfa, None)),
+            (_, Some(index)) => Ok(NFA::capture(nfa, index)),
+            (_, None) => Ok(nfa),
+        }
+    }
+
+    fn check_size(&self, states: usize) -> Result<(), ParseError> {
+        if states > self.config.size_limit {
+            return Err(ParseError::SizeLimitExceeded(self.config.size_limit))
+        }
+
+        Ok(())
+    }
+
     fn peek(&self) -> Option<&Token> {
         self.tokens.first()
     }
@@ -131,8 +297,32 @@ impl Parser {
     fn peek_postfix(&self) -> Option<Token> {
         match self.peek() {
             Some(Token::Star) => Some(Token::Star),
+            Some(Token::Plus) => Some(Token::Plus),
+            Some(Token::Question) => Some(Token::Question),
+            Some(&Token::Repeat { min, max }) => Some(Token::Repeat { min, max }),
             _ => None,
         }
     }
 
-}
\ No newline at end of file
+}
+
+// A possessive repetition of a single class stops at the first character
+// outside it, so it is the greedy repetition followed by a negative lookahead
+// for the class. `c?+` takes the character whenever it is there. Bounded
+// repetitions such as `c{2,4}+` have no such equivalent.
+fn possessive_equivalent(class: CharClass, postfix: &Token) -> Option<NFA> {
+    let min = match *postfix {
+        Token::Star => 0,
+        Token::Plus => 1,
+        Token::Repeat { min, max: None } => min,
+        Token::Question => {
+            let absent = NFA::look(NFA::class(class.clone()), true, true);
+            return Some(NFA::union(NFA::class(class), absent))
+        },
+        _ => return None,
+    };
+
+    let repeated = NFA::repetition(NFA::class(class.clone()), min, None, true);
+
+    Some(NFA::concatenate(repeated, NFA::look(NFA::class(class), true, true)))
+}
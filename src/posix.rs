@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use crate::look::{Context, LookTables};
+use crate::matcher::Slots;
+use crate::nfa::Transition;
+
+/// The structure of the pattern an NFA was built from, as far as the POSIX
+/// submatch rules need it. Every NFA constructor records its own, so
+/// leftmost-longest captures can be worked out without a syntax tree.
+/// Sequences and alternations are kept flat, so non-capturing groups leave
+/// no trace.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Shape {
+    #[default]
+    Empty,
+    /// A transition that consumes one character.
+    Char(Transition),
+    /// An assertion or look-around.
+    Zero(Transition),
+    Concat(Vec<Shape>),
+    Alternate(Vec<Shape>),
+    Repeat { shape: Box<Shape>, min: usize, max: Option<usize> },
+    Group { index: usize, shape: Box<Shape> },
+    /// Backreferences and atomic groups, which leftmost-longest matching rejects.
+    Unsupported,
+}
+
+impl Shape {
+    pub(crate) fn concat(first: Shape, second: Shape) -> Shape {
+        let mut shapes = first.into_concat();
+        shapes.extend(second.into_concat());
+        Shape::Concat(shapes)
+    }
+
+    pub(crate) fn alternate(first: Shape, second: Shape) -> Shape {
+        let mut shapes = first.into_alternate();
+        shapes.extend(second.into_alternate());
+        Shape::Alternate(shapes)
+    }
+
+    fn into_concat(self) -> Vec<Shape> {
+        match self {
+            Shape::Concat(shapes) => shapes,
+            shape => vec![shape],
+        }
+    }
+
+    fn into_alternate(self) -> Vec<Shape> {
+        match self {
+            Shape::Alternate(shapes) => shapes,
+            shape => vec![shape],
+        }
+    }
+
+    // The indices of the capture groups inside this shape.
+    fn groups(&self, groups: &mut Vec<usize>) {
+        match self {
+            Shape::Concat(shapes) | Shape::Alternate(shapes) => shapes.iter().for_each(|shape| shape.groups(groups)),
+            Shape::Repeat { shape, .. } => shape.groups(groups),
+            Shape::Group { index, shape } => {
+                groups.push(*index);
+                shape.groups(groups);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// Assigns the capture slots of a match of `shape` that spans exactly
+/// `start..end` of `input`, by the POSIX rules: each subexpression, from left
+/// to right, matches the longest string it can while the rest still matches.
+/// An alternation takes its leftmost alternative that matches, a repetition
+/// makes each iteration as long as it can, and a group inside a repetition
+/// reports its match in the last iteration only, or none if that iteration
+/// skipped it. A repetition that matches the empty string still iterates once
+/// if it can, so its groups match the empty string too.
+///
+/// The solver works out where a subexpression can end for a whole set of
+/// starts at once, and runs the iterations of a repetition in a loop, so it
+/// only recurses as deep as the pattern nests. Each pass over a set takes
+/// time linear in its size, and a pattern makes a pass for every node of the
+/// NFA it expands to, so in the worst case this takes time proportional to
+/// the length of the match times the size of that NFA: `(a{1,50}){1,40}`
+/// makes some 2000 passes. That is the bound of the search that found the
+/// match, which runs the same NFA at every position.
+pub(crate) fn captures(
+    shape: &Shape,
+    input: &str,
+    (start, end): (usize, usize),
+    looks: &LookTables,
+    slot_count: usize,
+) -> Option<Slots> {
+    let positions = input[start..end].char_indices().map(|(offset, _)| start + offset).chain([end]).collect();
+    let solver = Solver { input, looks, positions };
+    let last = solver.positions.len() - 1;
+
+    if !contains(&solver.ends(shape, 0, last), last) { return None }
+
+    let mut slots = vec![None; slot_count];
+    solver.assign(shape, 0, last, &mut slots);
+
+    Some(slots)
+}
+
+// Which way `Solver::extend` walks, and the position it must not go past.
+#[derive(Clone, Copy)]
+enum Direction {
+    Forward { to: usize },
+    Backward { from: usize },
+}
+
+// Positions below are indices into `positions`, the character boundaries of
+// the match, so that every one of them is a place a subexpression can start
+// or end. Sets of positions are sorted vectors.
+struct Solver<'a> {
+    input: &'a str,
+    looks: &'a LookTables,
+    positions: Vec<usize>,
+}
+
+impl Solver<'_> {
+    // Where `shape` can end when it starts at `from`, without passing `to`.
+    fn ends(&self, shape: &Shape, from: usize, to: usize) -> Vec<usize> {
+        self.extend(shape, &[from], Direction::Forward { to })
+    }
+
+    // Where `shape` can start, at or after `from`, to end at one of `ends`.
+    fn starts(&self, shape: &Shape, ends: &[usize], from: usize) -> Vec<usize> {
+        self.extend(shape, ends, Direction::Backward { from })
+    }
+
+    // Where `shape` can end when it starts at one of `positions` or, walking
+    // backward, where it can start to end at one of them.
+    fn extend(&self, shape: &Shape, positions: &[usize], direction: Direction) -> Vec<usize> {
+        if positions.is_empty() { return Vec::new() }
+
+        match shape {
+            Shape::Empty => positions.to_vec(),
+            Shape::Char(transition) => positions.iter().filter_map(|&position| {
+                let (before, reached) = match direction {
+                    Direction::Forward { to } => (position < to).then(|| (position, position + 1))?,
+                    Direction::Backward { from } => (position > from).then(|| (position - 1, position - 1))?,
+                };
+                let ch = self.input[self.positions[before]..].chars().next()?;
+                transition.matches(ch).then_some(reached)
+            }).collect(),
+            Shape::Zero(transition) => positions.iter().copied().filter(|&position| {
+                transition.epsilon_at(&Context::new(self.input, self.positions[position], self.looks))
+            }).collect(),
+            Shape::Concat(shapes) => {
+                let step = |reached: Vec<usize>, shape: &Shape| self.extend(shape, &reached, direction);
+                match direction {
+                    Direction::Forward { .. } => shapes.iter().fold(positions.to_vec(), step),
+                    Direction::Backward { .. } => shapes.iter().rev().fold(positions.to_vec(), step),
+                }
+            },
+            Shape::Alternate(shapes) => shapes.iter().fold(Vec::new(), |reached, shape| {
+                union(&reached, &self.extend(shape, positions, direction))
+            }),
+            Shape::Repeat { shape, min, max } => {
+                let mut reached = positions.to_vec();
+                for _ in 0..*min { reached = self.extend(shape, &reached, direction) }
+
+                match max {
+                    None => self.closure(shape, reached, direction),
+                    Some(max) => {
+                        let mut all = reached.clone();
+                        for _ in *min..*max {
+                            reached = self.extend(shape, &reached, direction);
+                            if reached.is_empty() { break }
+                            all = union(&all, &reached);
+                        }
+                        all
+                    },
+                }
+            },
+            Shape::Group { shape, .. } => self.extend(shape, positions, direction),
+            Shape::Unsupported => Vec::new(),
+        }
+    }
+
+    // Everything any number of iterations of `shape` reach from `positions`.
+    // Each round only extends the positions the one before found, so a
+    // position is visited once however many iterations lead to it.
+    fn closure(&self, shape: &Shape, positions: Vec<usize>, direction: Direction) -> Vec<usize> {
+        let mut seen: HashSet<usize> = positions.iter().copied().collect();
+        let mut frontier = positions;
+
+        while !frontier.is_empty() {
+            frontier = self.extend(shape, &frontier, direction).into_iter().filter(|&position| seen.insert(position)).collect();
+        }
+
+        let mut reached: Vec<usize> = seen.into_iter().collect();
+        reached.sort_unstable();
+        reached
+    }
+
+    // The furthest `shape` can reach from `from`, up to `to`, at an end that
+    // `accept` allows the rest of the pattern to go on from.
+    fn longest(&self, shape: &Shape, from: usize, to: usize, accept: impl Fn(usize) -> bool) -> Option<usize> {
+        self.ends(shape, from, to).into_iter().rev().find(|&middle| accept(middle))
+    }
+
+    // Sets the slots of every group in `shape` for its match between `from`
+    // and `to`, which must be one it can make.
+    fn assign(&self, shape: &Shape, from: usize, to: usize, slots: &mut Slots) {
+        match shape {
+            Shape::Concat(shapes) => {
+                // rests[index] is where the shapes after shapes[index] can
+                // start and still end at `to`.
+                let mut rests = vec![vec![to]];
+                for shape in shapes.iter().skip(1).rev() {
+                    let rest = self.starts(shape, &rests[rests.len() - 1], from);
+                    rests.push(rest);
+                }
+                rests.reverse();
+
+                let mut from = from;
+                for (shape, rest) in shapes.iter().zip(&rests) {
+                    let Some(middle) = self.longest(shape, from, to, |middle| contains(rest, middle)) else { return };
+                    self.assign(shape, from, middle, slots);
+                    from = middle;
+                }
+            },
+            Shape::Alternate(shapes) => {
+                if let Some(shape) = shapes.iter().find(|shape| contains(&self.ends(shape, from, to), to)) {
+                    self.assign(shape, from, to, slots);
+                }
+            },
+            Shape::Repeat { shape, min, max } => {
+                let remaining = Remaining::new(self, shape, *min, *max, from, to);
+                let (mut min, mut max, mut from) = (*min, *max, from);
+                let mut iterations = 0;
+                let mut groups = Vec::new();
+                shape.groups(&mut groups);
+
+                // Only the first `min` iterations may be empty, so the loop
+                // always moves on, bar the one null iteration.
+                while max != Some(0) {
+                    let middle = if min == 0 && from == to {
+                        if iterations > 0 || !contains(&self.ends(shape, from, to), from) { break }
+                        from
+                    } else {
+                        let (rest_min, rest_max) = (min.saturating_sub(1), max.map(|max| max - 1));
+                        let next = self.longest(shape, from, to, |middle| {
+                            (middle > from || min > 0) && remaining.allows(middle, rest_min, rest_max)
+                        });
+                        let Some(middle) = next else { break };
+                        middle
+                    };
+
+                    for &group in &groups {
+                        for slot in slots.iter_mut().skip(2 * group).take(2) { *slot = None; }
+                    }
+                    self.assign(shape, from, middle, slots);
+
+                    min = min.saturating_sub(1);
+                    max = max.map(|max| max - 1);
+                    from = middle;
+                    iterations += 1;
+                }
+            },
+            Shape::Group { index, shape } => {
+                if let Some(slot) = slots.get_mut(2 * index) { *slot = Some(self.positions[from]) }
+                if let Some(slot) = slots.get_mut(2 * index + 1) { *slot = Some(self.positions[to]) }
+                self.assign(shape, from, to, slots);
+            },
+            _ => {},
+        }
+    }
+}
+
+// Where the rest of a repetition can start and still end at `to`, by how
+// many iterations it has left.
+struct Remaining {
+    // exact[count] is where exactly `count` iterations reach `to` from. Counts
+    // past the end reach it from nowhere.
+    exact: Vec<Vec<usize>>,
+    // Where `min` or more iterations reach `to` from, when there is no maximum.
+    beyond: Vec<usize>,
+}
+
+impl Remaining {
+    fn new(solver: &Solver, shape: &Shape, min: usize, max: Option<usize>, from: usize, to: usize) -> Remaining {
+        let mut exact = vec![vec![to]];
+        for _ in 0..max.unwrap_or(min) {
+            let before = solver.starts(shape, &exact[exact.len() - 1], from);
+            if before.is_empty() { break }
+            exact.push(before);
+        }
+
+        let beyond = match (max, exact.get(min)) {
+            (None, Some(reached)) => solver.closure(shape, reached.clone(), Direction::Backward { from }),
+            _ => Vec::new(),
+        };
+
+        Remaining { exact, beyond }
+    }
+
+    // Whether between `min` and `max` more iterations can run from `position`.
+    fn allows(&self, position: usize, min: usize, max: Option<usize>) -> bool {
+        let counts = max.map_or(self.exact.len(), |max| max + 1);
+
+        self.exact.iter().take(counts).skip(min).any(|set| contains(set, position)) ||
+            (max.is_none() && contains(&self.beyond, position))
+    }
+}
+
+fn contains(positions: &[usize], position: usize) -> bool {
+    positions.binary_search(&position).is_ok()
+}
+
+fn union(first: &[usize], second: &[usize]) -> Vec<usize> {
+    let mut all = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.iter().peekable(), second.iter().peekable());
+
+    while let (Some(&&a), Some(&&b)) = (first.peek(), second.peek()) {
+        all.push(a.min(b));
+        if a <= b { first.next(); }
+        if b <= a { second.next(); }
+    }
+    all.extend(first.chain(second));
+
+    all
+}
@@ -1,10 +1,31 @@
+use crate::class::CharClass;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
     Literal(char),
+    Class(CharClass),
+    Dot,
+    Caret,
+    Dollar,
+    StartText,
+    EndText,
+    WordBoundary,
+    NotWordBoundary,
+    WordStart,
+    WordEnd,
+    Backref(usize),
+    NamedBackref(String),
     Union,
     Star,
+    Plus,
+    Question,
+    Repeat { min: usize, max: Option<usize> },
     LParen,
+    NamedGroup(String),
+    NonCapturing(Flags),
+    SetFlags(Flags),
+    Look { ahead: bool, negated: bool },
+    Atomic,
     RParen,
     Unknown(char)
 }
@@ -13,11 +34,79 @@ impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             Token::Literal(c) => write!(f, "{c}"),
+            Token::Class(class) => write!(f, "{class}"),
+            Token::Dot => write!(f, "."),
+            Token::Caret => write!(f, "^"),
+            Token::Dollar => write!(f, "$"),
+            Token::StartText => write!(f, "\\A"),
+            Token::EndText => write!(f, "\\z"),
+            Token::WordBoundary => write!(f, "\\b"),
+            Token::NotWordBoundary => write!(f, "\\B"),
+            Token::WordStart => write!(f, "\\<"),
+            Token::WordEnd => write!(f, "\\>"),
+            Token::Backref(group) => write!(f, "\\{group}"),
+            Token::NamedBackref(name) => write!(f, "\\k<{name}>"),
             Token::Union => write!(f, "|"),
             Token::Star => write!(f, "*"),
+            Token::Plus => write!(f, "+"),
+            Token::Question => write!(f, "?"),
+            Token::Repeat { min, max: Some(max) } if min == max => write!(f, "{{{min}}}"),
+            Token::Repeat { min, max: Some(max) } => write!(f, "{{{min},{max}}}"),
+            Token::Repeat { min, max: None } => write!(f, "{{{min},}}"),
             Token::LParen => write!(f, "("),
+            Token::NamedGroup(name) => write!(f, "(?<{name}>"),
+            Token::NonCapturing(flags) => write!(f, "(?{flags}:"),
+            Token::SetFlags(flags) => write!(f, "(?{flags})"),
+            Token::Atomic => write!(f, "(?>"),
+            Token::Look { ahead, negated } => {
+                let direction = if *ahead { "" } else { "<" };
+                let kind = if *negated { "!" } else { "=" };
+                write!(f, "(?{direction}{kind}")
+            },
             Token::RParen => write!(f, ")"),
             Token::Unknown(c) => write!(f, "{c}"),
         }
     }
-}
\ No newline at end of file
+}
+/// Flags set or cleared by an inline group such as `(?i-s:...)` or `(?m)`.
+/// `None` leaves the flag as it was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags {
+    pub case_insensitive: Option<bool>,
+    pub multi_line: Option<bool>,
+    pub dot_matches_new_line: Option<bool>,
+    pub ignore_whitespace: Option<bool>,
+}
+
+impl Flags {
+    pub(crate) fn get_mut(&mut self, flag: char) -> Option<&mut Option<bool>> {
+        match flag {
+            'i' => Some(&mut self.case_insensitive),
+            'm' => Some(&mut self.multi_line),
+            's' => Some(&mut self.dot_matches_new_line),
+            'x' => Some(&mut self.ignore_whitespace),
+            _ => None,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (char, Option<bool>)> {
+        [
+            ('i', self.case_insensitive),
+            ('m', self.multi_line),
+            ('s', self.dot_matches_new_line),
+            ('x', self.ignore_whitespace),
+        ].into_iter()
+    }
+}
+
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let enabled: String = self.iter().filter(|&(_, v)| v == Some(true)).map(|(c, _)| c).collect();
+        let disabled: String = self.iter().filter(|&(_, v)| v == Some(false)).map(|(c, _)| c).collect();
+
+        match disabled.is_empty() {
+            true => write!(f, "{enabled}"),
+            false => write!(f, "{enabled}-{disabled}"),
+        }
+    }
+}
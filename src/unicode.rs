@@ -0,0 +1,80 @@
+use crate::class::{self, CharClass};
+use crate::unicode_tables::{case_folding_simple, general_category, perl_word, property_bool, script};
+
+/// Resolves the name inside `\p{...}` to the class it stands for. Accepts
+/// general categories, scripts, binary properties and the special names
+/// `Any`, `ASCII` and `Assigned`, as well as the explicit `gc=...` and
+/// `sc=...` forms. Names are matched loosely, ignoring case, spaces,
+/// hyphens and underscores.
+pub(crate) fn property_class(name: &str) -> Option<CharClass> {
+    if let Some((property, value)) = name.split_once(['=', ':']) {
+        let value = normalize(value);
+
+        return match normalize(property).as_str() {
+            "gc" | "generalcategory" => lookup(general_category::ALIASES, general_category::BY_NAME, &value),
+            "sc" | "script" => lookup(script::ALIASES, script::BY_NAME, &value),
+            _ => None,
+        }
+    }
+
+    let name = normalize(name);
+
+    special_class(&name)
+        .or_else(|| lookup(general_category::ALIASES, general_category::BY_NAME, &name))
+        .or_else(|| lookup(script::ALIASES, script::BY_NAME, &name))
+        .or_else(|| lookup(property_bool::ALIASES, property_bool::BY_NAME, &name))
+}
+
+/// Whether `c` is in `\w`, looked up directly so word boundaries don't need a class.
+pub(crate) fn is_word_character(c: char, unicode: bool) -> bool {
+    match unicode {
+        true => class::ranges_contain(perl_word::PERL_WORD, c),
+        false => c.is_ascii_alphanumeric() || c == '_',
+    }
+}
+
+/// The case variants of every character in `start..=end` that has any, as
+/// given by the simple case folding table. 'k' yields 'K' and the Kelvin sign.
+pub(crate) fn simple_folds(start: char, end: char) -> impl Iterator<Item = char> {
+    let table = case_folding_simple::CASE_FOLDING_SIMPLE;
+    let first = table.partition_point(|&(c, _)| c < start);
+
+    table[first..]
+        .iter()
+        .take_while(move |&&(c, _)| c <= end)
+        .flat_map(|&(_, orbit)| orbit.iter().copied())
+}
+
+fn special_class(name: &str) -> Option<CharClass> {
+    match name {
+        "any" => Some(CharClass::new(vec![('\0', char::MAX)])),
+        "ascii" => Some(CharClass::new(vec![('\0', '\x7F')])),
+        "assigned" => lookup(general_category::ALIASES, general_category::BY_NAME, "unassigned")
+            .map(|unassigned| unassigned.negate()),
+        _ => None,
+    }
+}
+
+fn lookup(
+    aliases: &[(&str, &str)],
+    by_name: &[(&str, &'static [(char, char)])],
+    name: &str,
+) -> Option<CharClass> {
+    let canonical = aliases
+        .binary_search_by_key(&name, |&(alias, _)| alias)
+        .map(|i| aliases[i].1)
+        .ok()?;
+
+    by_name
+        .binary_search_by_key(&canonical, |&(name, _)| name)
+        .map(|i| CharClass::from_table(by_name[i].1))
+        .ok()
+}
+
+// Loose matching from UAX #44: case, whitespace, '-' and '_' are not significant.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,9 @@
+//! Unicode property tables, each a sorted list of inclusive scalar ranges,
+//! and the simple case folding table. All but this module are generated by
+//! `scripts/generate_unicode_tables.py`.
+
+pub(crate) mod case_folding_simple;
+pub(crate) mod general_category;
+pub(crate) mod perl_word;
+pub(crate) mod property_bool;
+pub(crate) mod script;
@@ -0,0 +1,1414 @@
+// Generated by scripts/generate_unicode_tables.py from the Unicode Character
+// Database, version 16.0.0, as tabulated by regex-syntax 0.8.5. Do not edit by hand.
+
+pub(crate) const BY_NAME: &[(&str, &[(char, char)])] = &[
+    ("Adlam", ADLAM),
+    ("Ahom", AHOM),
+    ("Anatolian_Hieroglyphs", ANATOLIAN_HIEROGLYPHS),
+    ("Arabic", ARABIC),
+    ("Armenian", ARMENIAN),
+    ("Avestan", AVESTAN),
+    ("Balinese", BALINESE),
+    ("Bamum", BAMUM),
+    ("Bassa_Vah", BASSA_VAH),
+    ("Batak", BATAK),
+    ("Bengali", BENGALI),
+    ("Bhaiksuki", BHAIKSUKI),
+    ("Bopomofo", BOPOMOFO),
+    ("Brahmi", BRAHMI),
+    ("Braille", BRAILLE),
+    ("Buginese", BUGINESE),
+    ("Buhid", BUHID),
+    ("Canadian_Aboriginal", CANADIAN_ABORIGINAL),
+    ("Carian", CARIAN),
+    ("Caucasian_Albanian", CAUCASIAN_ALBANIAN),
+    ("Chakma", CHAKMA),
+    ("Cham", CHAM),
+    ("Cherokee", CHEROKEE),
+    ("Chorasmian", CHORASMIAN),
+    ("Common", COMMON),
+    ("Coptic", COPTIC),
+    ("Cuneiform", CUNEIFORM),
+    ("Cypriot", CYPRIOT),
+    ("Cypro_Minoan", CYPRO_MINOAN),
+    ("Cyrillic", CYRILLIC),
+    ("Deseret", DESERET),
+    ("Devanagari", DEVANAGARI),
+    ("Dives_Akuru", DIVES_AKURU),
+    ("Dogra", DOGRA),
+    ("Duployan", DUPLOYAN),
+    ("Egyptian_Hieroglyphs", EGYPTIAN_HIEROGLYPHS),
+    ("Elbasan", ELBASAN),
+    ("Elymaic", ELYMAIC),
+    ("Ethiopic", ETHIOPIC),
+    ("Garay", GARAY),
+    ("Georgian", GEORGIAN),
+    ("Glagolitic", GLAGOLITIC),
+    ("Gothic", GOTHIC),
+    ("Grantha", GRANTHA),
+    ("Greek", GREEK),
+    ("Gujarati", GUJARATI),
+    ("Gunjala_Gondi", GUNJALA_GONDI),
+    ("Gurmukhi", GURMUKHI),
+    ("Gurung_Khema", GURUNG_KHEMA),
+    ("Han", HAN),
+    ("Hangul", HANGUL),
+    ("Hanifi_Rohingya", HANIFI_ROHINGYA),
+    ("Hanunoo", HANUNOO),
+    ("Hatran", HATRAN),
+    ("Hebrew", HEBREW),
+    ("Hiragana", HIRAGANA),
+    ("Imperial_Aramaic", IMPERIAL_ARAMAIC),
+    ("Inherited", INHERITED),
+    ("Inscriptional_Pahlavi", INSCRIPTIONAL_PAHLAVI),
+    ("Inscriptional_Parthian", INSCRIPTIONAL_PARTHIAN),
+    ("Javanese", JAVANESE),
+    ("Kaithi", KAITHI),
+    ("Kannada", KANNADA),
+    ("Katakana", KATAKANA),
+    ("Kawi", KAWI),
+    ("Kayah_Li", KAYAH_LI),
+    ("Kharoshthi", KHAROSHTHI),
+    ("Khitan_Small_Script", KHITAN_SMALL_SCRIPT),
+    ("Khmer", KHMER),
+    ("Khojki", KHOJKI),
+    ("Khudawadi", KHUDAWADI),
+    ("Kirat_Rai", KIRAT_RAI),
+    ("Lao", LAO),
+    ("Latin", LATIN),
+    ("Lepcha", LEPCHA),
+    ("Limbu", LIMBU),
+    ("Linear_A", LINEAR_A),
+    ("Linear_B", LINEAR_B),
+    ("Lisu", LISU),
+    ("Lycian", LYCIAN),
+    ("Lydian", LYDIAN),
+    ("Mahajani", MAHAJANI),
+    ("Makasar", MAKASAR),
+    ("Malayalam", MALAYALAM),
+    ("Mandaic", MANDAIC),
+    ("Manichaean", MANICHAEAN),
+    ("Marchen", MARCHEN),
+    ("Masaram_Gondi", MASARAM_GONDI),
+    ("Medefaidrin", MEDEFAIDRIN),
+    ("Meetei_Mayek", MEETEI_MAYEK),
+    ("Mende_Kikakui", MENDE_KIKAKUI),
+    ("Meroitic_Cursive", MEROITIC_CURSIVE),
+    ("Meroitic_Hieroglyphs", MEROITIC_HIEROGLYPHS),
+    ("Miao", MIAO),
+    ("Modi", MODI),
+    ("Mongolian", MONGOLIAN),
+    ("Mro", MRO),
+    ("Multani", MULTANI),
+    ("Myanmar", MYANMAR),
+    ("Nabataean", NABATAEAN),
+    ("Nag_Mundari", NAG_MUNDARI),
+    ("Nandinagari", NANDINAGARI),
+    ("New_Tai_Lue", NEW_TAI_LUE),
+    ("Newa", NEWA),
+    ("Nko", NKO),
+    ("Nushu", NUSHU),
+    ("Nyiakeng_Puachue_Hmong", NYIAKENG_PUACHUE_HMONG),
+    ("Ogham", OGHAM),
+    ("Ol_Chiki", OL_CHIKI),
+    ("Ol_Onal", OL_ONAL),
+    ("Old_Hungarian", OLD_HUNGARIAN),
+    ("Old_Italic", OLD_ITALIC),
+    ("Old_North_Arabian", OLD_NORTH_ARABIAN),
+    ("Old_Permic", OLD_PERMIC),
+    ("Old_Persian", OLD_PERSIAN),
+    ("Old_Sogdian", OLD_SOGDIAN),
+    ("Old_South_Arabian", OLD_SOUTH_ARABIAN),
+    ("Old_Turkic", OLD_TURKIC),
+    ("Old_Uyghur", OLD_UYGHUR),
+    ("Oriya", ORIYA),
+    ("Osage", OSAGE),
+    ("Osmanya", OSMANYA),
+    ("Pahawh_Hmong", PAHAWH_HMONG),
+    ("Palmyrene", PALMYRENE),
+    ("Pau_Cin_Hau", PAU_CIN_HAU),
+    ("Phags_Pa", PHAGS_PA),
+    ("Phoenician", PHOENICIAN),
+    ("Psalter_Pahlavi", PSALTER_PAHLAVI),
+    ("Rejang", REJANG),
+    ("Runic", RUNIC),
+    ("Samaritan", SAMARITAN),
+    ("Saurashtra", SAURASHTRA),
+    ("Sharada", SHARADA),
+    ("Shavian", SHAVIAN),
+    ("Siddham", SIDDHAM),
+    ("SignWriting", SIGNWRITING),
+    ("Sinhala", SINHALA),
+    ("Sogdian", SOGDIAN),
+    ("Sora_Sompeng", SORA_SOMPENG),
+    ("Soyombo", SOYOMBO),
+    ("Sundanese", SUNDANESE),
+    ("Sunuwar", SUNUWAR),
+    ("Syloti_Nagri", SYLOTI_NAGRI),
+    ("Syriac", SYRIAC),
+    ("Tagalog", TAGALOG),
+    ("Tagbanwa", TAGBANWA),
+    ("Tai_Le", TAI_LE),
+    ("Tai_Tham", TAI_THAM),
+    ("Tai_Viet", TAI_VIET),
+    ("Takri", TAKRI),
+    ("Tamil", TAMIL),
+    ("Tangsa", TANGSA),
+    ("Tangut", TANGUT),
+    ("Telugu", TELUGU),
+    ("Thaana", THAANA),
+    ("Thai", THAI),
+    ("Tibetan", TIBETAN),
+    ("Tifinagh", TIFINAGH),
+    ("Tirhuta", TIRHUTA),
+    ("Todhri", TODHRI),
+    ("Toto", TOTO),
+    ("Tulu_Tigalari", TULU_TIGALARI),
+    ("Ugaritic", UGARITIC),
+    ("Vai", VAI),
+    ("Vithkuqi", VITHKUQI),
+    ("Wancho", WANCHO),
+    ("Warang_Citi", WARANG_CITI),
+    ("Yezidi", YEZIDI),
+    ("Yi", YI),
+    ("Zanabazar_Square", ZANABAZAR_SQUARE),
+];
+
+/// Loosely matched aliases (lowercase, no spaces, hyphens or underscores) to canonical names.
+pub(crate) const ALIASES: &[(&str, &str)] = &[
+    ("adlam", "Adlam"),
+    ("adlm", "Adlam"),
+    ("aghb", "Caucasian_Albanian"),
+    ("ahom", "Ahom"),
+    ("anatolianhieroglyphs", "Anatolian_Hieroglyphs"),
+    ("arab", "Arabic"),
+    ("arabic", "Arabic"),
+    ("armenian", "Armenian"),
+    ("armi", "Imperial_Aramaic"),
+    ("armn", "Armenian"),
+    ("avestan", "Avestan"),
+    ("avst", "Avestan"),
+    ("bali", "Balinese"),
+    ("balinese", "Balinese"),
+    ("bamu", "Bamum"),
+    ("bamum", "Bamum"),
+    ("bass", "Bassa_Vah"),
+    ("bassavah", "Bassa_Vah"),
+    ("batak", "Batak"),
+    ("batk", "Batak"),
+    ("beng", "Bengali"),
+    ("bengali", "Bengali"),
+    ("bhaiksuki", "Bhaiksuki"),
+    ("bhks", "Bhaiksuki"),
+    ("bopo", "Bopomofo"),
+    ("bopomofo", "Bopomofo"),
+    ("brah", "Brahmi"),
+    ("brahmi", "Brahmi"),
+    ("brai", "Braille"),
+    ("braille", "Braille"),
+    ("bugi", "Buginese"),
+    ("buginese", "Buginese"),
+    ("buhd", "Buhid"),
+    ("buhid", "Buhid"),
+    ("cakm", "Chakma"),
+    ("canadianaboriginal", "Canadian_Aboriginal"),
+    ("cans", "Canadian_Aboriginal"),
+    ("cari", "Carian"),
+    ("carian", "Carian"),
+    ("caucasianalbanian", "Caucasian_Albanian"),
+    ("chakma", "Chakma"),
+    ("cham", "Cham"),
+    ("cher", "Cherokee"),
+    ("cherokee", "Cherokee"),
+    ("chorasmian", "Chorasmian"),
+    ("chrs", "Chorasmian"),
+    ("common", "Common"),
+    ("copt", "Coptic"),
+    ("coptic", "Coptic"),
+    ("cpmn", "Cypro_Minoan"),
+    ("cprt", "Cypriot"),
+    ("cuneiform", "Cuneiform"),
+    ("cypriot", "Cypriot"),
+    ("cyprominoan", "Cypro_Minoan"),
+    ("cyrillic", "Cyrillic"),
+    ("cyrl", "Cyrillic"),
+    ("deseret", "Deseret"),
+    ("deva", "Devanagari"),
+    ("devanagari", "Devanagari"),
+    ("diak", "Dives_Akuru"),
+    ("divesakuru", "Dives_Akuru"),
+    ("dogr", "Dogra"),
+    ("dogra", "Dogra"),
+    ("dsrt", "Deseret"),
+    ("dupl", "Duployan"),
+    ("duployan", "Duployan"),
+    ("egyp", "Egyptian_Hieroglyphs"),
+    ("egyptianhieroglyphs", "Egyptian_Hieroglyphs"),
+    ("elba", "Elbasan"),
+    ("elbasan", "Elbasan"),
+    ("elym", "Elymaic"),
+    ("elymaic", "Elymaic"),
+    ("ethi", "Ethiopic"),
+    ("ethiopic", "Ethiopic"),
+    ("gara", "Garay"),
+    ("garay", "Garay"),
+    ("geor", "Georgian"),
+    ("georgian", "Georgian"),
+    ("glag", "Glagolitic"),
+    ("glagolitic", "Glagolitic"),
+    ("gong", "Gunjala_Gondi"),
+    ("gonm", "Masaram_Gondi"),
+    ("goth", "Gothic"),
+    ("gothic", "Gothic"),
+    ("gran", "Grantha"),
+    ("grantha", "Grantha"),
+    ("greek", "Greek"),
+    ("grek", "Greek"),
+    ("gujarati", "Gujarati"),
+    ("gujr", "Gujarati"),
+    ("gukh", "Gurung_Khema"),
+    ("gunjalagondi", "Gunjala_Gondi"),
+    ("gurmukhi", "Gurmukhi"),
+    ("guru", "Gurmukhi"),
+    ("gurungkhema", "Gurung_Khema"),
+    ("han", "Han"),
+    ("hang", "Hangul"),
+    ("hangul", "Hangul"),
+    ("hani", "Han"),
+    ("hanifirohingya", "Hanifi_Rohingya"),
+    ("hano", "Hanunoo"),
+    ("hanunoo", "Hanunoo"),
+    ("hatr", "Hatran"),
+    ("hatran", "Hatran"),
+    ("hebr", "Hebrew"),
+    ("hebrew", "Hebrew"),
+    ("hira", "Hiragana"),
+    ("hiragana", "Hiragana"),
+    ("hluw", "Anatolian_Hieroglyphs"),
+    ("hmng", "Pahawh_Hmong"),
+    ("hmnp", "Nyiakeng_Puachue_Hmong"),
+    ("hung", "Old_Hungarian"),
+    ("imperialaramaic", "Imperial_Aramaic"),
+    ("inherited", "Inherited"),
+    ("inscriptionalpahlavi", "Inscriptional_Pahlavi"),
+    ("inscriptionalparthian", "Inscriptional_Parthian"),
+    ("ital", "Old_Italic"),
+    ("java", "Javanese"),
+    ("javanese", "Javanese"),
+    ("kaithi", "Kaithi"),
+    ("kali", "Kayah_Li"),
+    ("kana", "Katakana"),
+    ("kannada", "Kannada"),
+    ("katakana", "Katakana"),
+    ("kawi", "Kawi"),
+    ("kayahli", "Kayah_Li"),
+    ("khar", "Kharoshthi"),
+    ("kharoshthi", "Kharoshthi"),
+    ("khitansmallscript", "Khitan_Small_Script"),
+    ("khmer", "Khmer"),
+    ("khmr", "Khmer"),
+    ("khoj", "Khojki"),
+    ("khojki", "Khojki"),
+    ("khudawadi", "Khudawadi"),
+    ("kiratrai", "Kirat_Rai"),
+    ("kits", "Khitan_Small_Script"),
+    ("knda", "Kannada"),
+    ("krai", "Kirat_Rai"),
+    ("kthi", "Kaithi"),
+    ("lana", "Tai_Tham"),
+    ("lao", "Lao"),
+    ("laoo", "Lao"),
+    ("latin", "Latin"),
+    ("latn", "Latin"),
+    ("lepc", "Lepcha"),
+    ("lepcha", "Lepcha"),
+    ("limb", "Limbu"),
+    ("limbu", "Limbu"),
+    ("lina", "Linear_A"),
+    ("linb", "Linear_B"),
+    ("lineara", "Linear_A"),
+    ("linearb", "Linear_B"),
+    ("lisu", "Lisu"),
+    ("lyci", "Lycian"),
+    ("lycian", "Lycian"),
+    ("lydi", "Lydian"),
+    ("lydian", "Lydian"),
+    ("mahajani", "Mahajani"),
+    ("mahj", "Mahajani"),
+    ("maka", "Makasar"),
+    ("makasar", "Makasar"),
+    ("malayalam", "Malayalam"),
+    ("mand", "Mandaic"),
+    ("mandaic", "Mandaic"),
+    ("mani", "Manichaean"),
+    ("manichaean", "Manichaean"),
+    ("marc", "Marchen"),
+    ("marchen", "Marchen"),
+    ("masaramgondi", "Masaram_Gondi"),
+    ("medefaidrin", "Medefaidrin"),
+    ("medf", "Medefaidrin"),
+    ("meeteimayek", "Meetei_Mayek"),
+    ("mend", "Mende_Kikakui"),
+    ("mendekikakui", "Mende_Kikakui"),
+    ("merc", "Meroitic_Cursive"),
+    ("mero", "Meroitic_Hieroglyphs"),
+    ("meroiticcursive", "Meroitic_Cursive"),
+    ("meroitichieroglyphs", "Meroitic_Hieroglyphs"),
+    ("miao", "Miao"),
+    ("mlym", "Malayalam"),
+    ("modi", "Modi"),
+    ("mong", "Mongolian"),
+    ("mongolian", "Mongolian"),
+    ("mro", "Mro"),
+    ("mroo", "Mro"),
+    ("mtei", "Meetei_Mayek"),
+    ("mult", "Multani"),
+    ("multani", "Multani"),
+    ("myanmar", "Myanmar"),
+    ("mymr", "Myanmar"),
+    ("nabataean", "Nabataean"),
+    ("nagm", "Nag_Mundari"),
+    ("nagmundari", "Nag_Mundari"),
+    ("nand", "Nandinagari"),
+    ("nandinagari", "Nandinagari"),
+    ("narb", "Old_North_Arabian"),
+    ("nbat", "Nabataean"),
+    ("newa", "Newa"),
+    ("newtailue", "New_Tai_Lue"),
+    ("nko", "Nko"),
+    ("nkoo", "Nko"),
+    ("nshu", "Nushu"),
+    ("nushu", "Nushu"),
+    ("nyiakengpuachuehmong", "Nyiakeng_Puachue_Hmong"),
+    ("ogam", "Ogham"),
+    ("ogham", "Ogham"),
+    ("olchiki", "Ol_Chiki"),
+    ("olck", "Ol_Chiki"),
+    ("oldhungarian", "Old_Hungarian"),
+    ("olditalic", "Old_Italic"),
+    ("oldnortharabian", "Old_North_Arabian"),
+    ("oldpermic", "Old_Permic"),
+    ("oldpersian", "Old_Persian"),
+    ("oldsogdian", "Old_Sogdian"),
+    ("oldsoutharabian", "Old_South_Arabian"),
+    ("oldturkic", "Old_Turkic"),
+    ("olduyghur", "Old_Uyghur"),
+    ("olonal", "Ol_Onal"),
+    ("onao", "Ol_Onal"),
+    ("oriya", "Oriya"),
+    ("orkh", "Old_Turkic"),
+    ("orya", "Oriya"),
+    ("osage", "Osage"),
+    ("osge", "Osage"),
+    ("osma", "Osmanya"),
+    ("osmanya", "Osmanya"),
+    ("ougr", "Old_Uyghur"),
+    ("pahawhhmong", "Pahawh_Hmong"),
+    ("palm", "Palmyrene"),
+    ("palmyrene", "Palmyrene"),
+    ("pauc", "Pau_Cin_Hau"),
+    ("paucinhau", "Pau_Cin_Hau"),
+    ("perm", "Old_Permic"),
+    ("phag", "Phags_Pa"),
+    ("phagspa", "Phags_Pa"),
+    ("phli", "Inscriptional_Pahlavi"),
+    ("phlp", "Psalter_Pahlavi"),
+    ("phnx", "Phoenician"),
+    ("phoenician", "Phoenician"),
+    ("plrd", "Miao"),
+    ("prti", "Inscriptional_Parthian"),
+    ("psalterpahlavi", "Psalter_Pahlavi"),
+    ("qaac", "Coptic"),
+    ("qaai", "Inherited"),
+    ("rejang", "Rejang"),
+    ("rjng", "Rejang"),
+    ("rohg", "Hanifi_Rohingya"),
+    ("runic", "Runic"),
+    ("runr", "Runic"),
+    ("samaritan", "Samaritan"),
+    ("samr", "Samaritan"),
+    ("sarb", "Old_South_Arabian"),
+    ("saur", "Saurashtra"),
+    ("saurashtra", "Saurashtra"),
+    ("sgnw", "SignWriting"),
+    ("sharada", "Sharada"),
+    ("shavian", "Shavian"),
+    ("shaw", "Shavian"),
+    ("shrd", "Sharada"),
+    ("sidd", "Siddham"),
+    ("siddham", "Siddham"),
+    ("signwriting", "SignWriting"),
+    ("sind", "Khudawadi"),
+    ("sinh", "Sinhala"),
+    ("sinhala", "Sinhala"),
+    ("sogd", "Sogdian"),
+    ("sogdian", "Sogdian"),
+    ("sogo", "Old_Sogdian"),
+    ("sora", "Sora_Sompeng"),
+    ("sorasompeng", "Sora_Sompeng"),
+    ("soyo", "Soyombo"),
+    ("soyombo", "Soyombo"),
+    ("sund", "Sundanese"),
+    ("sundanese", "Sundanese"),
+    ("sunu", "Sunuwar"),
+    ("sunuwar", "Sunuwar"),
+    ("sylo", "Syloti_Nagri"),
+    ("sylotinagri", "Syloti_Nagri"),
+    ("syrc", "Syriac"),
+    ("syriac", "Syriac"),
+    ("tagalog", "Tagalog"),
+    ("tagb", "Tagbanwa"),
+    ("tagbanwa", "Tagbanwa"),
+    ("taile", "Tai_Le"),
+    ("taitham", "Tai_Tham"),
+    ("taiviet", "Tai_Viet"),
+    ("takr", "Takri"),
+    ("takri", "Takri"),
+    ("tale", "Tai_Le"),
+    ("talu", "New_Tai_Lue"),
+    ("tamil", "Tamil"),
+    ("taml", "Tamil"),
+    ("tang", "Tangut"),
+    ("tangsa", "Tangsa"),
+    ("tangut", "Tangut"),
+    ("tavt", "Tai_Viet"),
+    ("telu", "Telugu"),
+    ("telugu", "Telugu"),
+    ("tfng", "Tifinagh"),
+    ("tglg", "Tagalog"),
+    ("thaa", "Thaana"),
+    ("thaana", "Thaana"),
+    ("thai", "Thai"),
+    ("tibetan", "Tibetan"),
+    ("tibt", "Tibetan"),
+    ("tifinagh", "Tifinagh"),
+    ("tirh", "Tirhuta"),
+    ("tirhuta", "Tirhuta"),
+    ("tnsa", "Tangsa"),
+    ("todhri", "Todhri"),
+    ("todr", "Todhri"),
+    ("toto", "Toto"),
+    ("tulutigalari", "Tulu_Tigalari"),
+    ("tutg", "Tulu_Tigalari"),
+    ("ugar", "Ugaritic"),
+    ("ugaritic", "Ugaritic"),
+    ("vai", "Vai"),
+    ("vaii", "Vai"),
+    ("vith", "Vithkuqi"),
+    ("vithkuqi", "Vithkuqi"),
+    ("wancho", "Wancho"),
+    ("wara", "Warang_Citi"),
+    ("warangciti", "Warang_Citi"),
+    ("wcho", "Wancho"),
+    ("xpeo", "Old_Persian"),
+    ("xsux", "Cuneiform"),
+    ("yezi", "Yezidi"),
+    ("yezidi", "Yezidi"),
+    ("yi", "Yi"),
+    ("yiii", "Yi"),
+    ("zanabazarsquare", "Zanabazar_Square"),
+    ("zanb", "Zanabazar_Square"),
+    ("zinh", "Inherited"),
+    ("zyyy", "Common"),
+];
+
+pub(crate) const ADLAM: &[(char, char)] = &[
+    ('\u{1e900}', '\u{1e94b}'), ('\u{1e950}', '\u{1e959}'), ('\u{1e95e}', '\u{1e95f}'),
+];
+
+pub(crate) const AHOM: &[(char, char)] = &[
+    ('\u{11700}', '\u{1171a}'), ('\u{1171d}', '\u{1172b}'), ('\u{11730}', '\u{11746}'),
+];
+
+pub(crate) const ANATOLIAN_HIEROGLYPHS: &[(char, char)] = &[
+    ('\u{14400}', '\u{14646}'),
+];
+
+pub(crate) const ARABIC: &[(char, char)] = &[
+    ('\u{600}', '\u{604}'), ('\u{606}', '\u{60b}'), ('\u{60d}', '\u{61a}'),
+    ('\u{61c}', '\u{61e}'), ('\u{620}', '\u{63f}'), ('\u{641}', '\u{64a}'),
+    ('\u{656}', '\u{66f}'), ('\u{671}', '\u{6dc}'), ('\u{6de}', '\u{6ff}'),
+    ('\u{750}', '\u{77f}'), ('\u{870}', '\u{88e}'), ('\u{890}', '\u{891}'),
+    ('\u{897}', '\u{8e1}'), ('\u{8e3}', '\u{8ff}'), ('\u{fb50}', '\u{fbc2}'),
+    ('\u{fbd3}', '\u{fd3d}'), ('\u{fd40}', '\u{fd8f}'), ('\u{fd92}', '\u{fdc7}'),
+    ('\u{fdcf}', '\u{fdcf}'), ('\u{fdf0}', '\u{fdff}'), ('\u{fe70}', '\u{fe74}'),
+    ('\u{fe76}', '\u{fefc}'), ('\u{10e60}', '\u{10e7e}'), ('\u{10ec2}', '\u{10ec4}'),
+    ('\u{10efc}', '\u{10eff}'), ('\u{1ee00}', '\u{1ee03}'), ('\u{1ee05}', '\u{1ee1f}'),
+    ('\u{1ee21}', '\u{1ee22}'), ('\u{1ee24}', '\u{1ee24}'), ('\u{1ee27}', '\u{1ee27}'),
+    ('\u{1ee29}', '\u{1ee32}'), ('\u{1ee34}', '\u{1ee37}'), ('\u{1ee39}', '\u{1ee39}'),
+    ('\u{1ee3b}', '\u{1ee3b}'), ('\u{1ee42}', '\u{1ee42}'), ('\u{1ee47}', '\u{1ee47}'),
+    ('\u{1ee49}', '\u{1ee49}'), ('\u{1ee4b}', '\u{1ee4b}'), ('\u{1ee4d}', '\u{1ee4f}'),
+    ('\u{1ee51}', '\u{1ee52}'), ('\u{1ee54}', '\u{1ee54}'), ('\u{1ee57}', '\u{1ee57}'),
+    ('\u{1ee59}', '\u{1ee59}'), ('\u{1ee5b}', '\u{1ee5b}'), ('\u{1ee5d}', '\u{1ee5d}'),
+    ('\u{1ee5f}', '\u{1ee5f}'), ('\u{1ee61}', '\u{1ee62}'), ('\u{1ee64}', '\u{1ee64}'),
+    ('\u{1ee67}', '\u{1ee6a}'), ('\u{1ee6c}', '\u{1ee72}'), ('\u{1ee74}', '\u{1ee77}'),
+    ('\u{1ee79}', '\u{1ee7c}'), ('\u{1ee7e}', '\u{1ee7e}'), ('\u{1ee80}', '\u{1ee89}'),
+    ('\u{1ee8b}', '\u{1ee9b}'), ('\u{1eea1}', '\u{1eea3}'), ('\u{1eea5}', '\u{1eea9}'),
+    ('\u{1eeab}', '\u{1eebb}'), ('\u{1eef0}', '\u{1eef1}'),
+];
+
+pub(crate) const ARMENIAN: &[(char, char)] = &[
+    ('\u{531}', '\u{556}'), ('\u{559}', '\u{58a}'), ('\u{58d}', '\u{58f}'),
+    ('\u{fb13}', '\u{fb17}'),
+];
+
+pub(crate) const AVESTAN: &[(char, char)] = &[
+    ('\u{10b00}', '\u{10b35}'), ('\u{10b39}', '\u{10b3f}'),
+];
+
+pub(crate) const BALINESE: &[(char, char)] = &[
+    ('\u{1b00}', '\u{1b4c}'), ('\u{1b4e}', '\u{1b7f}'),
+];
+
+pub(crate) const BAMUM: &[(char, char)] = &[
+    ('\u{a6a0}', '\u{a6f7}'), ('\u{16800}', '\u{16a38}'),
+];
+
+pub(crate) const BASSA_VAH: &[(char, char)] = &[
+    ('\u{16ad0}', '\u{16aed}'), ('\u{16af0}', '\u{16af5}'),
+];
+
+pub(crate) const BATAK: &[(char, char)] = &[
+    ('\u{1bc0}', '\u{1bf3}'), ('\u{1bfc}', '\u{1bff}'),
+];
+
+pub(crate) const BENGALI: &[(char, char)] = &[
+    ('\u{980}', '\u{983}'), ('\u{985}', '\u{98c}'), ('\u{98f}', '\u{990}'),
+    ('\u{993}', '\u{9a8}'), ('\u{9aa}', '\u{9b0}'), ('\u{9b2}', '\u{9b2}'),
+    ('\u{9b6}', '\u{9b9}'), ('\u{9bc}', '\u{9c4}'), ('\u{9c7}', '\u{9c8}'),
+    ('\u{9cb}', '\u{9ce}'), ('\u{9d7}', '\u{9d7}'), ('\u{9dc}', '\u{9dd}'),
+    ('\u{9df}', '\u{9e3}'), ('\u{9e6}', '\u{9fe}'),
+];
+
+pub(crate) const BHAIKSUKI: &[(char, char)] = &[
+    ('\u{11c00}', '\u{11c08}'), ('\u{11c0a}', '\u{11c36}'), ('\u{11c38}', '\u{11c45}'),
+    ('\u{11c50}', '\u{11c6c}'),
+];
+
+pub(crate) const BOPOMOFO: &[(char, char)] = &[
+    ('\u{2ea}', '\u{2eb}'), ('\u{3105}', '\u{312f}'), ('\u{31a0}', '\u{31bf}'),
+];
+
+pub(crate) const BRAHMI: &[(char, char)] = &[
+    ('\u{11000}', '\u{1104d}'), ('\u{11052}', '\u{11075}'), ('\u{1107f}', '\u{1107f}'),
+];
+
+pub(crate) const BRAILLE: &[(char, char)] = &[
+    ('\u{2800}', '\u{28ff}'),
+];
+
+pub(crate) const BUGINESE: &[(char, char)] = &[
+    ('\u{1a00}', '\u{1a1b}'), ('\u{1a1e}', '\u{1a1f}'),
+];
+
+pub(crate) const BUHID: &[(char, char)] = &[
+    ('\u{1740}', '\u{1753}'),
+];
+
+pub(crate) const CANADIAN_ABORIGINAL: &[(char, char)] = &[
+    ('\u{1400}', '\u{167f}'), ('\u{18b0}', '\u{18f5}'), ('\u{11ab0}', '\u{11abf}'),
+];
+
+pub(crate) const CARIAN: &[(char, char)] = &[
+    ('\u{102a0}', '\u{102d0}'),
+];
+
+pub(crate) const CAUCASIAN_ALBANIAN: &[(char, char)] = &[
+    ('\u{10530}', '\u{10563}'), ('\u{1056f}', '\u{1056f}'),
+];
+
+pub(crate) const CHAKMA: &[(char, char)] = &[
+    ('\u{11100}', '\u{11134}'), ('\u{11136}', '\u{11147}'),
+];
+
+pub(crate) const CHAM: &[(char, char)] = &[
+    ('\u{aa00}', '\u{aa36}'), ('\u{aa40}', '\u{aa4d}'), ('\u{aa50}', '\u{aa59}'),
+    ('\u{aa5c}', '\u{aa5f}'),
+];
+
+pub(crate) const CHEROKEE: &[(char, char)] = &[
+    ('\u{13a0}', '\u{13f5}'), ('\u{13f8}', '\u{13fd}'), ('\u{ab70}', '\u{abbf}'),
+];
+
+pub(crate) const CHORASMIAN: &[(char, char)] = &[
+    ('\u{10fb0}', '\u{10fcb}'),
+];
+
+pub(crate) const COMMON: &[(char, char)] = &[
+    ('\u{0}', '\u{40}'), ('\u{5b}', '\u{60}'), ('\u{7b}', '\u{a9}'),
+    ('\u{ab}', '\u{b9}'), ('\u{bb}', '\u{bf}'), ('\u{d7}', '\u{d7}'),
+    ('\u{f7}', '\u{f7}'), ('\u{2b9}', '\u{2df}'), ('\u{2e5}', '\u{2e9}'),
+    ('\u{2ec}', '\u{2ff}'), ('\u{374}', '\u{374}'), ('\u{37e}', '\u{37e}'),
+    ('\u{385}', '\u{385}'), ('\u{387}', '\u{387}'), ('\u{605}', '\u{605}'),
+    ('\u{60c}', '\u{60c}'), ('\u{61b}', '\u{61b}'), ('\u{61f}', '\u{61f}'),
+    ('\u{640}', '\u{640}'), ('\u{6dd}', '\u{6dd}'), ('\u{8e2}', '\u{8e2}'),
+    ('\u{964}', '\u{965}'), ('\u{e3f}', '\u{e3f}'), ('\u{fd5}', '\u{fd8}'),
+    ('\u{10fb}', '\u{10fb}'), ('\u{16eb}', '\u{16ed}'), ('\u{1735}', '\u{1736}'),
+    ('\u{1802}', '\u{1803}'), ('\u{1805}', '\u{1805}'), ('\u{1cd3}', '\u{1cd3}'),
+    ('\u{1ce1}', '\u{1ce1}'), ('\u{1ce9}', '\u{1cec}'), ('\u{1cee}', '\u{1cf3}'),
+    ('\u{1cf5}', '\u{1cf7}'), ('\u{1cfa}', '\u{1cfa}'), ('\u{2000}', '\u{200b}'),
+    ('\u{200e}', '\u{2064}'), ('\u{2066}', '\u{2070}'), ('\u{2074}', '\u{207e}'),
+    ('\u{2080}', '\u{208e}'), ('\u{20a0}', '\u{20c0}'), ('\u{2100}', '\u{2125}'),
+    ('\u{2127}', '\u{2129}'), ('\u{212c}', '\u{2131}'), ('\u{2133}', '\u{214d}'),
+    ('\u{214f}', '\u{215f}'), ('\u{2189}', '\u{218b}'), ('\u{2190}', '\u{2429}'),
+    ('\u{2440}', '\u{244a}'), ('\u{2460}', '\u{27ff}'), ('\u{2900}', '\u{2b73}'),
+    ('\u{2b76}', '\u{2b95}'), ('\u{2b97}', '\u{2bff}'), ('\u{2e00}', '\u{2e5d}'),
+    ('\u{2ff0}', '\u{3004}'), ('\u{3006}', '\u{3006}'), ('\u{3008}', '\u{3020}'),
+    ('\u{3030}', '\u{3037}'), ('\u{303c}', '\u{303f}'), ('\u{309b}', '\u{309c}'),
+    ('\u{30a0}', '\u{30a0}'), ('\u{30fb}', '\u{30fc}'), ('\u{3190}', '\u{319f}'),
+    ('\u{31c0}', '\u{31e5}'), ('\u{31ef}', '\u{31ef}'), ('\u{3220}', '\u{325f}'),
+    ('\u{327f}', '\u{32cf}'), ('\u{32ff}', '\u{32ff}'), ('\u{3358}', '\u{33ff}'),
+    ('\u{4dc0}', '\u{4dff}'), ('\u{a700}', '\u{a721}'), ('\u{a788}', '\u{a78a}'),
+    ('\u{a830}', '\u{a839}'), ('\u{a92e}', '\u{a92e}'), ('\u{a9cf}', '\u{a9cf}'),
+    ('\u{ab5b}', '\u{ab5b}'), ('\u{ab6a}', '\u{ab6b}'), ('\u{fd3e}', '\u{fd3f}'),
+    ('\u{fe10}', '\u{fe19}'), ('\u{fe30}', '\u{fe52}'), ('\u{fe54}', '\u{fe66}'),
+    ('\u{fe68}', '\u{fe6b}'), ('\u{feff}', '\u{feff}'), ('\u{ff01}', '\u{ff20}'),
+    ('\u{ff3b}', '\u{ff40}'), ('\u{ff5b}', '\u{ff65}'), ('\u{ff70}', '\u{ff70}'),
+    ('\u{ff9e}', '\u{ff9f}'), ('\u{ffe0}', '\u{ffe6}'), ('\u{ffe8}', '\u{ffee}'),
+    ('\u{fff9}', '\u{fffd}'), ('\u{10100}', '\u{10102}'), ('\u{10107}', '\u{10133}'),
+    ('\u{10137}', '\u{1013f}'), ('\u{10190}', '\u{1019c}'), ('\u{101d0}', '\u{101fc}'),
+    ('\u{102e1}', '\u{102fb}'), ('\u{1bca0}', '\u{1bca3}'), ('\u{1cc00}', '\u{1ccf9}'),
+    ('\u{1cd00}', '\u{1ceb3}'), ('\u{1cf50}', '\u{1cfc3}'), ('\u{1d000}', '\u{1d0f5}'),
+    ('\u{1d100}', '\u{1d126}'), ('\u{1d129}', '\u{1d166}'), ('\u{1d16a}', '\u{1d17a}'),
+    ('\u{1d183}', '\u{1d184}'), ('\u{1d18c}', '\u{1d1a9}'), ('\u{1d1ae}', '\u{1d1ea}'),
+    ('\u{1d2c0}', '\u{1d2d3}'), ('\u{1d2e0}', '\u{1d2f3}'), ('\u{1d300}', '\u{1d356}'),
+    ('\u{1d360}', '\u{1d378}'), ('\u{1d400}', '\u{1d454}'), ('\u{1d456}', '\u{1d49c}'),
+    ('\u{1d49e}', '\u{1d49f}'), ('\u{1d4a2}', '\u{1d4a2}'), ('\u{1d4a5}', '\u{1d4a6}'),
+    ('\u{1d4a9}', '\u{1d4ac}'), ('\u{1d4ae}', '\u{1d4b9}'), ('\u{1d4bb}', '\u{1d4bb}'),
+    ('\u{1d4bd}', '\u{1d4c3}'), ('\u{1d4c5}', '\u{1d505}'), ('\u{1d507}', '\u{1d50a}'),
+    ('\u{1d50d}', '\u{1d514}'), ('\u{1d516}', '\u{1d51c}'), ('\u{1d51e}', '\u{1d539}'),
+    ('\u{1d53b}', '\u{1d53e}'), ('\u{1d540}', '\u{1d544}'), ('\u{1d546}', '\u{1d546}'),
+    ('\u{1d54a}', '\u{1d550}'), ('\u{1d552}', '\u{1d6a5}'), ('\u{1d6a8}', '\u{1d7cb}'),
+    ('\u{1d7ce}', '\u{1d7ff}'), ('\u{1ec71}', '\u{1ecb4}'), ('\u{1ed01}', '\u{1ed3d}'),
+    ('\u{1f000}', '\u{1f02b}'), ('\u{1f030}', '\u{1f093}'), ('\u{1f0a0}', '\u{1f0ae}'),
+    ('\u{1f0b1}', '\u{1f0bf}'), ('\u{1f0c1}', '\u{1f0cf}'), ('\u{1f0d1}', '\u{1f0f5}'),
+    ('\u{1f100}', '\u{1f1ad}'), ('\u{1f1e6}', '\u{1f1ff}'), ('\u{1f201}', '\u{1f202}'),
+    ('\u{1f210}', '\u{1f23b}'), ('\u{1f240}', '\u{1f248}'), ('\u{1f250}', '\u{1f251}'),
+    ('\u{1f260}', '\u{1f265}'), ('\u{1f300}', '\u{1f6d7}'), ('\u{1f6dc}', '\u{1f6ec}'),
+    ('\u{1f6f0}', '\u{1f6fc}'), ('\u{1f700}', '\u{1f776}'), ('\u{1f77b}', '\u{1f7d9}'),
+    ('\u{1f7e0}', '\u{1f7eb}'), ('\u{1f7f0}', '\u{1f7f0}'), ('\u{1f800}', '\u{1f80b}'),
+    ('\u{1f810}', '\u{1f847}'), ('\u{1f850}', '\u{1f859}'), ('\u{1f860}', '\u{1f887}'),
+    ('\u{1f890}', '\u{1f8ad}'), ('\u{1f8b0}', '\u{1f8bb}'), ('\u{1f8c0}', '\u{1f8c1}'),
+    ('\u{1f900}', '\u{1fa53}'), ('\u{1fa60}', '\u{1fa6d}'), ('\u{1fa70}', '\u{1fa7c}'),
+    ('\u{1fa80}', '\u{1fa89}'), ('\u{1fa8f}', '\u{1fac6}'), ('\u{1face}', '\u{1fadc}'),
+    ('\u{1fadf}', '\u{1fae9}'), ('\u{1faf0}', '\u{1faf8}'), ('\u{1fb00}', '\u{1fb92}'),
+    ('\u{1fb94}', '\u{1fbf9}'), ('\u{e0001}', '\u{e0001}'), ('\u{e0020}', '\u{e007f}'),
+];
+
+pub(crate) const COPTIC: &[(char, char)] = &[
+    ('\u{3e2}', '\u{3ef}'), ('\u{2c80}', '\u{2cf3}'), ('\u{2cf9}', '\u{2cff}'),
+];
+
+pub(crate) const CUNEIFORM: &[(char, char)] = &[
+    ('\u{12000}', '\u{12399}'), ('\u{12400}', '\u{1246e}'), ('\u{12470}', '\u{12474}'),
+    ('\u{12480}', '\u{12543}'),
+];
+
+pub(crate) const CYPRIOT: &[(char, char)] = &[
+    ('\u{10800}', '\u{10805}'), ('\u{10808}', '\u{10808}'), ('\u{1080a}', '\u{10835}'),
+    ('\u{10837}', '\u{10838}'), ('\u{1083c}', '\u{1083c}'), ('\u{1083f}', '\u{1083f}'),
+];
+
+pub(crate) const CYPRO_MINOAN: &[(char, char)] = &[
+    ('\u{12f90}', '\u{12ff2}'),
+];
+
+pub(crate) const CYRILLIC: &[(char, char)] = &[
+    ('\u{400}', '\u{484}'), ('\u{487}', '\u{52f}'), ('\u{1c80}', '\u{1c8a}'),
+    ('\u{1d2b}', '\u{1d2b}'), ('\u{1d78}', '\u{1d78}'), ('\u{2de0}', '\u{2dff}'),
+    ('\u{a640}', '\u{a69f}'), ('\u{fe2e}', '\u{fe2f}'), ('\u{1e030}', '\u{1e06d}'),
+    ('\u{1e08f}', '\u{1e08f}'),
+];
+
+pub(crate) const DESERET: &[(char, char)] = &[
+    ('\u{10400}', '\u{1044f}'),
+];
+
+pub(crate) const DEVANAGARI: &[(char, char)] = &[
+    ('\u{900}', '\u{950}'), ('\u{955}', '\u{963}'), ('\u{966}', '\u{97f}'),
+    ('\u{a8e0}', '\u{a8ff}'), ('\u{11b00}', '\u{11b09}'),
+];
+
+pub(crate) const DIVES_AKURU: &[(char, char)] = &[
+    ('\u{11900}', '\u{11906}'), ('\u{11909}', '\u{11909}'), ('\u{1190c}', '\u{11913}'),
+    ('\u{11915}', '\u{11916}'), ('\u{11918}', '\u{11935}'), ('\u{11937}', '\u{11938}'),
+    ('\u{1193b}', '\u{11946}'), ('\u{11950}', '\u{11959}'),
+];
+
+pub(crate) const DOGRA: &[(char, char)] = &[
+    ('\u{11800}', '\u{1183b}'),
+];
+
+pub(crate) const DUPLOYAN: &[(char, char)] = &[
+    ('\u{1bc00}', '\u{1bc6a}'), ('\u{1bc70}', '\u{1bc7c}'), ('\u{1bc80}', '\u{1bc88}'),
+    ('\u{1bc90}', '\u{1bc99}'), ('\u{1bc9c}', '\u{1bc9f}'),
+];
+
+pub(crate) const EGYPTIAN_HIEROGLYPHS: &[(char, char)] = &[
+    ('\u{13000}', '\u{13455}'), ('\u{13460}', '\u{143fa}'),
+];
+
+pub(crate) const ELBASAN: &[(char, char)] = &[
+    ('\u{10500}', '\u{10527}'),
+];
+
+pub(crate) const ELYMAIC: &[(char, char)] = &[
+    ('\u{10fe0}', '\u{10ff6}'),
+];
+
+pub(crate) const ETHIOPIC: &[(char, char)] = &[
+    ('\u{1200}', '\u{1248}'), ('\u{124a}', '\u{124d}'), ('\u{1250}', '\u{1256}'),
+    ('\u{1258}', '\u{1258}'), ('\u{125a}', '\u{125d}'), ('\u{1260}', '\u{1288}'),
+    ('\u{128a}', '\u{128d}'), ('\u{1290}', '\u{12b0}'), ('\u{12b2}', '\u{12b5}'),
+    ('\u{12b8}', '\u{12be}'), ('\u{12c0}', '\u{12c0}'), ('\u{12c2}', '\u{12c5}'),
+    ('\u{12c8}', '\u{12d6}'), ('\u{12d8}', '\u{1310}'), ('\u{1312}', '\u{1315}'),
+    ('\u{1318}', '\u{135a}'), ('\u{135d}', '\u{137c}'), ('\u{1380}', '\u{1399}'),
+    ('\u{2d80}', '\u{2d96}'), ('\u{2da0}', '\u{2da6}'), ('\u{2da8}', '\u{2dae}'),
+    ('\u{2db0}', '\u{2db6}'), ('\u{2db8}', '\u{2dbe}'), ('\u{2dc0}', '\u{2dc6}'),
+    ('\u{2dc8}', '\u{2dce}'), ('\u{2dd0}', '\u{2dd6}'), ('\u{2dd8}', '\u{2dde}'),
+    ('\u{ab01}', '\u{ab06}'), ('\u{ab09}', '\u{ab0e}'), ('\u{ab11}', '\u{ab16}'),
+    ('\u{ab20}', '\u{ab26}'), ('\u{ab28}', '\u{ab2e}'), ('\u{1e7e0}', '\u{1e7e6}'),
+    ('\u{1e7e8}', '\u{1e7eb}'), ('\u{1e7ed}', '\u{1e7ee}'), ('\u{1e7f0}', '\u{1e7fe}'),
+];
+
+pub(crate) const GARAY: &[(char, char)] = &[
+    ('\u{10d40}', '\u{10d65}'), ('\u{10d69}', '\u{10d85}'), ('\u{10d8e}', '\u{10d8f}'),
+];
+
+pub(crate) const GEORGIAN: &[(char, char)] = &[
+    ('\u{10a0}', '\u{10c5}'), ('\u{10c7}', '\u{10c7}'), ('\u{10cd}', '\u{10cd}'),
+    ('\u{10d0}', '\u{10fa}'), ('\u{10fc}', '\u{10ff}'), ('\u{1c90}', '\u{1cba}'),
+    ('\u{1cbd}', '\u{1cbf}'), ('\u{2d00}', '\u{2d25}'), ('\u{2d27}', '\u{2d27}'),
+    ('\u{2d2d}', '\u{2d2d}'),
+];
+
+pub(crate) const GLAGOLITIC: &[(char, char)] = &[
+    ('\u{2c00}', '\u{2c5f}'), ('\u{1e000}', '\u{1e006}'), ('\u{1e008}', '\u{1e018}'),
+    ('\u{1e01b}', '\u{1e021}'), ('\u{1e023}', '\u{1e024}'), ('\u{1e026}', '\u{1e02a}'),
+];
+
+pub(crate) const GOTHIC: &[(char, char)] = &[
+    ('\u{10330}', '\u{1034a}'),
+];
+
+pub(crate) const GRANTHA: &[(char, char)] = &[
+    ('\u{11300}', '\u{11303}'), ('\u{11305}', '\u{1130c}'), ('\u{1130f}', '\u{11310}'),
+    ('\u{11313}', '\u{11328}'), ('\u{1132a}', '\u{11330}'), ('\u{11332}', '\u{11333}'),
+    ('\u{11335}', '\u{11339}'), ('\u{1133c}', '\u{11344}'), ('\u{11347}', '\u{11348}'),
+    ('\u{1134b}', '\u{1134d}'), ('\u{11350}', '\u{11350}'), ('\u{11357}', '\u{11357}'),
+    ('\u{1135d}', '\u{11363}'), ('\u{11366}', '\u{1136c}'), ('\u{11370}', '\u{11374}'),
+];
+
+pub(crate) const GREEK: &[(char, char)] = &[
+    ('\u{370}', '\u{373}'), ('\u{375}', '\u{377}'), ('\u{37a}', '\u{37d}'),
+    ('\u{37f}', '\u{37f}'), ('\u{384}', '\u{384}'), ('\u{386}', '\u{386}'),
+    ('\u{388}', '\u{38a}'), ('\u{38c}', '\u{38c}'), ('\u{38e}', '\u{3a1}'),
+    ('\u{3a3}', '\u{3e1}'), ('\u{3f0}', '\u{3ff}'), ('\u{1d26}', '\u{1d2a}'),
+    ('\u{1d5d}', '\u{1d61}'), ('\u{1d66}', '\u{1d6a}'), ('\u{1dbf}', '\u{1dbf}'),
+    ('\u{1f00}', '\u{1f15}'), ('\u{1f18}', '\u{1f1d}'), ('\u{1f20}', '\u{1f45}'),
+    ('\u{1f48}', '\u{1f4d}'), ('\u{1f50}', '\u{1f57}'), ('\u{1f59}', '\u{1f59}'),
+    ('\u{1f5b}', '\u{1f5b}'), ('\u{1f5d}', '\u{1f5d}'), ('\u{1f5f}', '\u{1f7d}'),
+    ('\u{1f80}', '\u{1fb4}'), ('\u{1fb6}', '\u{1fc4}'), ('\u{1fc6}', '\u{1fd3}'),
+    ('\u{1fd6}', '\u{1fdb}'), ('\u{1fdd}', '\u{1fef}'), ('\u{1ff2}', '\u{1ff4}'),
+    ('\u{1ff6}', '\u{1ffe}'), ('\u{2126}', '\u{2126}'), ('\u{ab65}', '\u{ab65}'),
+    ('\u{10140}', '\u{1018e}'), ('\u{101a0}', '\u{101a0}'), ('\u{1d200}', '\u{1d245}'),
+];
+
+pub(crate) const GUJARATI: &[(char, char)] = &[
+    ('\u{a81}', '\u{a83}'), ('\u{a85}', '\u{a8d}'), ('\u{a8f}', '\u{a91}'),
+    ('\u{a93}', '\u{aa8}'), ('\u{aaa}', '\u{ab0}'), ('\u{ab2}', '\u{ab3}'),
+    ('\u{ab5}', '\u{ab9}'), ('\u{abc}', '\u{ac5}'), ('\u{ac7}', '\u{ac9}'),
+    ('\u{acb}', '\u{acd}'), ('\u{ad0}', '\u{ad0}'), ('\u{ae0}', '\u{ae3}'),
+    ('\u{ae6}', '\u{af1}'), ('\u{af9}', '\u{aff}'),
+];
+
+pub(crate) const GUNJALA_GONDI: &[(char, char)] = &[
+    ('\u{11d60}', '\u{11d65}'), ('\u{11d67}', '\u{11d68}'), ('\u{11d6a}', '\u{11d8e}'),
+    ('\u{11d90}', '\u{11d91}'), ('\u{11d93}', '\u{11d98}'), ('\u{11da0}', '\u{11da9}'),
+];
+
+pub(crate) const GURMUKHI: &[(char, char)] = &[
+    ('\u{a01}', '\u{a03}'), ('\u{a05}', '\u{a0a}'), ('\u{a0f}', '\u{a10}'),
+    ('\u{a13}', '\u{a28}'), ('\u{a2a}', '\u{a30}'), ('\u{a32}', '\u{a33}'),
+    ('\u{a35}', '\u{a36}'), ('\u{a38}', '\u{a39}'), ('\u{a3c}', '\u{a3c}'),
+    ('\u{a3e}', '\u{a42}'), ('\u{a47}', '\u{a48}'), ('\u{a4b}', '\u{a4d}'),
+    ('\u{a51}', '\u{a51}'), ('\u{a59}', '\u{a5c}'), ('\u{a5e}', '\u{a5e}'),
+    ('\u{a66}', '\u{a76}'),
+];
+
+pub(crate) const GURUNG_KHEMA: &[(char, char)] = &[
+    ('\u{16100}', '\u{16139}'),
+];
+
+pub(crate) const HAN: &[(char, char)] = &[
+    ('\u{2e80}', '\u{2e99}'), ('\u{2e9b}', '\u{2ef3}'), ('\u{2f00}', '\u{2fd5}'),
+    ('\u{3005}', '\u{3005}'), ('\u{3007}', '\u{3007}'), ('\u{3021}', '\u{3029}'),
+    ('\u{3038}', '\u{303b}'), ('\u{3400}', '\u{4dbf}'), ('\u{4e00}', '\u{9fff}'),
+    ('\u{f900}', '\u{fa6d}'), ('\u{fa70}', '\u{fad9}'), ('\u{16fe2}', '\u{16fe3}'),
+    ('\u{16ff0}', '\u{16ff1}'), ('\u{20000}', '\u{2a6df}'), ('\u{2a700}', '\u{2b739}'),
+    ('\u{2b740}', '\u{2b81d}'), ('\u{2b820}', '\u{2cea1}'), ('\u{2ceb0}', '\u{2ebe0}'),
+    ('\u{2ebf0}', '\u{2ee5d}'), ('\u{2f800}', '\u{2fa1d}'), ('\u{30000}', '\u{3134a}'),
+    ('\u{31350}', '\u{323af}'),
+];
+
+pub(crate) const HANGUL: &[(char, char)] = &[
+    ('\u{1100}', '\u{11ff}'), ('\u{302e}', '\u{302f}'), ('\u{3131}', '\u{318e}'),
+    ('\u{3200}', '\u{321e}'), ('\u{3260}', '\u{327e}'), ('\u{a960}', '\u{a97c}'),
+    ('\u{ac00}', '\u{d7a3}'), ('\u{d7b0}', '\u{d7c6}'), ('\u{d7cb}', '\u{d7fb}'),
+    ('\u{ffa0}', '\u{ffbe}'), ('\u{ffc2}', '\u{ffc7}'), ('\u{ffca}', '\u{ffcf}'),
+    ('\u{ffd2}', '\u{ffd7}'), ('\u{ffda}', '\u{ffdc}'),
+];
+
+pub(crate) const HANIFI_ROHINGYA: &[(char, char)] = &[
+    ('\u{10d00}', '\u{10d27}'), ('\u{10d30}', '\u{10d39}'),
+];
+
+pub(crate) const HANUNOO: &[(char, char)] = &[
+    ('\u{1720}', '\u{1734}'),
+];
+
+pub(crate) const HATRAN: &[(char, char)] = &[
+    ('\u{108e0}', '\u{108f2}'), ('\u{108f4}', '\u{108f5}'), ('\u{108fb}', '\u{108ff}'),
+];
+
+pub(crate) const HEBREW: &[(char, char)] = &[
+    ('\u{591}', '\u{5c7}'), ('\u{5d0}', '\u{5ea}'), ('\u{5ef}', '\u{5f4}'),
+    ('\u{fb1d}', '\u{fb36}'), ('\u{fb38}', '\u{fb3c}'), ('\u{fb3e}', '\u{fb3e}'),
+    ('\u{fb40}', '\u{fb41}'), ('\u{fb43}', '\u{fb44}'), ('\u{fb46}', '\u{fb4f}'),
+];
+
+pub(crate) const HIRAGANA: &[(char, char)] = &[
+    ('\u{3041}', '\u{3096}'), ('\u{309d}', '\u{309f}'), ('\u{1b001}', '\u{1b11f}'),
+    ('\u{1b132}', '\u{1b132}'), ('\u{1b150}', '\u{1b152}'), ('\u{1f200}', '\u{1f200}'),
+];
+
+pub(crate) const IMPERIAL_ARAMAIC: &[(char, char)] = &[
+    ('\u{10840}', '\u{10855}'), ('\u{10857}', '\u{1085f}'),
+];
+
+pub(crate) const INHERITED: &[(char, char)] = &[
+    ('\u{300}', '\u{36f}'), ('\u{485}', '\u{486}'), ('\u{64b}', '\u{655}'),
+    ('\u{670}', '\u{670}'), ('\u{951}', '\u{954}'), ('\u{1ab0}', '\u{1ace}'),
+    ('\u{1cd0}', '\u{1cd2}'), ('\u{1cd4}', '\u{1ce0}'), ('\u{1ce2}', '\u{1ce8}'),
+    ('\u{1ced}', '\u{1ced}'), ('\u{1cf4}', '\u{1cf4}'), ('\u{1cf8}', '\u{1cf9}'),
+    ('\u{1dc0}', '\u{1dff}'), ('\u{200c}', '\u{200d}'), ('\u{20d0}', '\u{20f0}'),
+    ('\u{302a}', '\u{302d}'), ('\u{3099}', '\u{309a}'), ('\u{fe00}', '\u{fe0f}'),
+    ('\u{fe20}', '\u{fe2d}'), ('\u{101fd}', '\u{101fd}'), ('\u{102e0}', '\u{102e0}'),
+    ('\u{1133b}', '\u{1133b}'), ('\u{1cf00}', '\u{1cf2d}'), ('\u{1cf30}', '\u{1cf46}'),
+    ('\u{1d167}', '\u{1d169}'), ('\u{1d17b}', '\u{1d182}'), ('\u{1d185}', '\u{1d18b}'),
+    ('\u{1d1aa}', '\u{1d1ad}'), ('\u{e0100}', '\u{e01ef}'),
+];
+
+pub(crate) const INSCRIPTIONAL_PAHLAVI: &[(char, char)] = &[
+    ('\u{10b60}', '\u{10b72}'), ('\u{10b78}', '\u{10b7f}'),
+];
+
+pub(crate) const INSCRIPTIONAL_PARTHIAN: &[(char, char)] = &[
+    ('\u{10b40}', '\u{10b55}'), ('\u{10b58}', '\u{10b5f}'),
+];
+
+pub(crate) const JAVANESE: &[(char, char)] = &[
+    ('\u{a980}', '\u{a9cd}'), ('\u{a9d0}', '\u{a9d9}'), ('\u{a9de}', '\u{a9df}'),
+];
+
+pub(crate) const KAITHI: &[(char, char)] = &[
+    ('\u{11080}', '\u{110c2}'), ('\u{110cd}', '\u{110cd}'),
+];
+
+pub(crate) const KANNADA: &[(char, char)] = &[
+    ('\u{c80}', '\u{c8c}'), ('\u{c8e}', '\u{c90}'), ('\u{c92}', '\u{ca8}'),
+    ('\u{caa}', '\u{cb3}'), ('\u{cb5}', '\u{cb9}'), ('\u{cbc}', '\u{cc4}'),
+    ('\u{cc6}', '\u{cc8}'), ('\u{cca}', '\u{ccd}'), ('\u{cd5}', '\u{cd6}'),
+    ('\u{cdd}', '\u{cde}'), ('\u{ce0}', '\u{ce3}'), ('\u{ce6}', '\u{cef}'),
+    ('\u{cf1}', '\u{cf3}'),
+];
+
+pub(crate) const KATAKANA: &[(char, char)] = &[
+    ('\u{30a1}', '\u{30fa}'), ('\u{30fd}', '\u{30ff}'), ('\u{31f0}', '\u{31ff}'),
+    ('\u{32d0}', '\u{32fe}'), ('\u{3300}', '\u{3357}'), ('\u{ff66}', '\u{ff6f}'),
+    ('\u{ff71}', '\u{ff9d}'), ('\u{1aff0}', '\u{1aff3}'), ('\u{1aff5}', '\u{1affb}'),
+    ('\u{1affd}', '\u{1affe}'), ('\u{1b000}', '\u{1b000}'), ('\u{1b120}', '\u{1b122}'),
+    ('\u{1b155}', '\u{1b155}'), ('\u{1b164}', '\u{1b167}'),
+];
+
+pub(crate) const KAWI: &[(char, char)] = &[
+    ('\u{11f00}', '\u{11f10}'), ('\u{11f12}', '\u{11f3a}'), ('\u{11f3e}', '\u{11f5a}'),
+];
+
+pub(crate) const KAYAH_LI: &[(char, char)] = &[
+    ('\u{a900}', '\u{a92d}'), ('\u{a92f}', '\u{a92f}'),
+];
+
+pub(crate) const KHAROSHTHI: &[(char, char)] = &[
+    ('\u{10a00}', '\u{10a03}'), ('\u{10a05}', '\u{10a06}'), ('\u{10a0c}', '\u{10a13}'),
+    ('\u{10a15}', '\u{10a17}'), ('\u{10a19}', '\u{10a35}'), ('\u{10a38}', '\u{10a3a}'),
+    ('\u{10a3f}', '\u{10a48}'), ('\u{10a50}', '\u{10a58}'),
+];
+
+pub(crate) const KHITAN_SMALL_SCRIPT: &[(char, char)] = &[
+    ('\u{16fe4}', '\u{16fe4}'), ('\u{18b00}', '\u{18cd5}'), ('\u{18cff}', '\u{18cff}'),
+];
+
+pub(crate) const KHMER: &[(char, char)] = &[
+    ('\u{1780}', '\u{17dd}'), ('\u{17e0}', '\u{17e9}'), ('\u{17f0}', '\u{17f9}'),
+    ('\u{19e0}', '\u{19ff}'),
+];
+
+pub(crate) const KHOJKI: &[(char, char)] = &[
+    ('\u{11200}', '\u{11211}'), ('\u{11213}', '\u{11241}'),
+];
+
+pub(crate) const KHUDAWADI: &[(char, char)] = &[
+    ('\u{112b0}', '\u{112ea}'), ('\u{112f0}', '\u{112f9}'),
+];
+
+pub(crate) const KIRAT_RAI: &[(char, char)] = &[
+    ('\u{16d40}', '\u{16d79}'),
+];
+
+pub(crate) const LAO: &[(char, char)] = &[
+    ('\u{e81}', '\u{e82}'), ('\u{e84}', '\u{e84}'), ('\u{e86}', '\u{e8a}'),
+    ('\u{e8c}', '\u{ea3}'), ('\u{ea5}', '\u{ea5}'), ('\u{ea7}', '\u{ebd}'),
+    ('\u{ec0}', '\u{ec4}'), ('\u{ec6}', '\u{ec6}'), ('\u{ec8}', '\u{ece}'),
+    ('\u{ed0}', '\u{ed9}'), ('\u{edc}', '\u{edf}'),
+];
+
+pub(crate) const LATIN: &[(char, char)] = &[
+    ('\u{41}', '\u{5a}'), ('\u{61}', '\u{7a}'), ('\u{aa}', '\u{aa}'),
+    ('\u{ba}', '\u{ba}'), ('\u{c0}', '\u{d6}'), ('\u{d8}', '\u{f6}'),
+    ('\u{f8}', '\u{2b8}'), ('\u{2e0}', '\u{2e4}'), ('\u{1d00}', '\u{1d25}'),
+    ('\u{1d2c}', '\u{1d5c}'), ('\u{1d62}', '\u{1d65}'), ('\u{1d6b}', '\u{1d77}'),
+    ('\u{1d79}', '\u{1dbe}'), ('\u{1e00}', '\u{1eff}'), ('\u{2071}', '\u{2071}'),
+    ('\u{207f}', '\u{207f}'), ('\u{2090}', '\u{209c}'), ('\u{212a}', '\u{212b}'),
+    ('\u{2132}', '\u{2132}'), ('\u{214e}', '\u{214e}'), ('\u{2160}', '\u{2188}'),
+    ('\u{2c60}', '\u{2c7f}'), ('\u{a722}', '\u{a787}'), ('\u{a78b}', '\u{a7cd}'),
+    ('\u{a7d0}', '\u{a7d1}'), ('\u{a7d3}', '\u{a7d3}'), ('\u{a7d5}', '\u{a7dc}'),
+    ('\u{a7f2}', '\u{a7ff}'), ('\u{ab30}', '\u{ab5a}'), ('\u{ab5c}', '\u{ab64}'),
+    ('\u{ab66}', '\u{ab69}'), ('\u{fb00}', '\u{fb06}'), ('\u{ff21}', '\u{ff3a}'),
+    ('\u{ff41}', '\u{ff5a}'), ('\u{10780}', '\u{10785}'), ('\u{10787}', '\u{107b0}'),
+    ('\u{107b2}', '\u{107ba}'), ('\u{1df00}', '\u{1df1e}'), ('\u{1df25}', '\u{1df2a}'),
+];
+
+pub(crate) const LEPCHA: &[(char, char)] = &[
+    ('\u{1c00}', '\u{1c37}'), ('\u{1c3b}', '\u{1c49}'), ('\u{1c4d}', '\u{1c4f}'),
+];
+
+pub(crate) const LIMBU: &[(char, char)] = &[
+    ('\u{1900}', '\u{191e}'), ('\u{1920}', '\u{192b}'), ('\u{1930}', '\u{193b}'),
+    ('\u{1940}', '\u{1940}'), ('\u{1944}', '\u{194f}'),
+];
+
+pub(crate) const LINEAR_A: &[(char, char)] = &[
+    ('\u{10600}', '\u{10736}'), ('\u{10740}', '\u{10755}'), ('\u{10760}', '\u{10767}'),
+];
+
+pub(crate) const LINEAR_B: &[(char, char)] = &[
+    ('\u{10000}', '\u{1000b}'), ('\u{1000d}', '\u{10026}'), ('\u{10028}', '\u{1003a}'),
+    ('\u{1003c}', '\u{1003d}'), ('\u{1003f}', '\u{1004d}'), ('\u{10050}', '\u{1005d}'),
+    ('\u{10080}', '\u{100fa}'),
+];
+
+pub(crate) const LISU: &[(char, char)] = &[
+    ('\u{a4d0}', '\u{a4ff}'), ('\u{11fb0}', '\u{11fb0}'),
+];
+
+pub(crate) const LYCIAN: &[(char, char)] = &[
+    ('\u{10280}', '\u{1029c}'),
+];
+
+pub(crate) const LYDIAN: &[(char, char)] = &[
+    ('\u{10920}', '\u{10939}'), ('\u{1093f}', '\u{1093f}'),
+];
+
+pub(crate) const MAHAJANI: &[(char, char)] = &[
+    ('\u{11150}', '\u{11176}'),
+];
+
+pub(crate) const MAKASAR: &[(char, char)] = &[
+    ('\u{11ee0}', '\u{11ef8}'),
+];
+
+pub(crate) const MALAYALAM: &[(char, char)] = &[
+    ('\u{d00}', '\u{d0c}'), ('\u{d0e}', '\u{d10}'), ('\u{d12}', '\u{d44}'),
+    ('\u{d46}', '\u{d48}'), ('\u{d4a}', '\u{d4f}'), ('\u{d54}', '\u{d63}'),
+    ('\u{d66}', '\u{d7f}'),
+];
+
+pub(crate) const MANDAIC: &[(char, char)] = &[
+    ('\u{840}', '\u{85b}'), ('\u{85e}', '\u{85e}'),
+];
+
+pub(crate) const MANICHAEAN: &[(char, char)] = &[
+    ('\u{10ac0}', '\u{10ae6}'), ('\u{10aeb}', '\u{10af6}'),
+];
+
+pub(crate) const MARCHEN: &[(char, char)] = &[
+    ('\u{11c70}', '\u{11c8f}'), ('\u{11c92}', '\u{11ca7}'), ('\u{11ca9}', '\u{11cb6}'),
+];
+
+pub(crate) const MASARAM_GONDI: &[(char, char)] = &[
+    ('\u{11d00}', '\u{11d06}'), ('\u{11d08}', '\u{11d09}'), ('\u{11d0b}', '\u{11d36}'),
+    ('\u{11d3a}', '\u{11d3a}'), ('\u{11d3c}', '\u{11d3d}'), ('\u{11d3f}', '\u{11d47}'),
+    ('\u{11d50}', '\u{11d59}'),
+];
+
+pub(crate) const MEDEFAIDRIN: &[(char, char)] = &[
+    ('\u{16e40}', '\u{16e9a}'),
+];
+
+pub(crate) const MEETEI_MAYEK: &[(char, char)] = &[
+    ('\u{aae0}', '\u{aaf6}'), ('\u{abc0}', '\u{abed}'), ('\u{abf0}', '\u{abf9}'),
+];
+
+pub(crate) const MENDE_KIKAKUI: &[(char, char)] = &[
+    ('\u{1e800}', '\u{1e8c4}'), ('\u{1e8c7}', '\u{1e8d6}'),
+];
+
+pub(crate) const MEROITIC_CURSIVE: &[(char, char)] = &[
+    ('\u{109a0}', '\u{109b7}'), ('\u{109bc}', '\u{109cf}'), ('\u{109d2}', '\u{109ff}'),
+];
+
+pub(crate) const MEROITIC_HIEROGLYPHS: &[(char, char)] = &[
+    ('\u{10980}', '\u{1099f}'),
+];
+
+pub(crate) const MIAO: &[(char, char)] = &[
+    ('\u{16f00}', '\u{16f4a}'), ('\u{16f4f}', '\u{16f87}'), ('\u{16f8f}', '\u{16f9f}'),
+];
+
+pub(crate) const MODI: &[(char, char)] = &[
+    ('\u{11600}', '\u{11644}'), ('\u{11650}', '\u{11659}'),
+];
+
+pub(crate) const MONGOLIAN: &[(char, char)] = &[
+    ('\u{1800}', '\u{1801}'), ('\u{1804}', '\u{1804}'), ('\u{1806}', '\u{1819}'),
+    ('\u{1820}', '\u{1878}'), ('\u{1880}', '\u{18aa}'), ('\u{11660}', '\u{1166c}'),
+];
+
+pub(crate) const MRO: &[(char, char)] = &[
+    ('\u{16a40}', '\u{16a5e}'), ('\u{16a60}', '\u{16a69}'), ('\u{16a6e}', '\u{16a6f}'),
+];
+
+pub(crate) const MULTANI: &[(char, char)] = &[
+    ('\u{11280}', '\u{11286}'), ('\u{11288}', '\u{11288}'), ('\u{1128a}', '\u{1128d}'),
+    ('\u{1128f}', '\u{1129d}'), ('\u{1129f}', '\u{112a9}'),
+];
+
+pub(crate) const MYANMAR: &[(char, char)] = &[
+    ('\u{1000}', '\u{109f}'), ('\u{a9e0}', '\u{a9fe}'), ('\u{aa60}', '\u{aa7f}'),
+    ('\u{116d0}', '\u{116e3}'),
+];
+
+pub(crate) const NABATAEAN: &[(char, char)] = &[
+    ('\u{10880}', '\u{1089e}'), ('\u{108a7}', '\u{108af}'),
+];
+
+pub(crate) const NAG_MUNDARI: &[(char, char)] = &[
+    ('\u{1e4d0}', '\u{1e4f9}'),
+];
+
+pub(crate) const NANDINAGARI: &[(char, char)] = &[
+    ('\u{119a0}', '\u{119a7}'), ('\u{119aa}', '\u{119d7}'), ('\u{119da}', '\u{119e4}'),
+];
+
+pub(crate) const NEW_TAI_LUE: &[(char, char)] = &[
+    ('\u{1980}', '\u{19ab}'), ('\u{19b0}', '\u{19c9}'), ('\u{19d0}', '\u{19da}'),
+    ('\u{19de}', '\u{19df}'),
+];
+
+pub(crate) const NEWA: &[(char, char)] = &[
+    ('\u{11400}', '\u{1145b}'), ('\u{1145d}', '\u{11461}'),
+];
+
+pub(crate) const NKO: &[(char, char)] = &[
+    ('\u{7c0}', '\u{7fa}'), ('\u{7fd}', '\u{7ff}'),
+];
+
+pub(crate) const NUSHU: &[(char, char)] = &[
+    ('\u{16fe1}', '\u{16fe1}'), ('\u{1b170}', '\u{1b2fb}'),
+];
+
+pub(crate) const NYIAKENG_PUACHUE_HMONG: &[(char, char)] = &[
+    ('\u{1e100}', '\u{1e12c}'), ('\u{1e130}', '\u{1e13d}'), ('\u{1e140}', '\u{1e149}'),
+    ('\u{1e14e}', '\u{1e14f}'),
+];
+
+pub(crate) const OGHAM: &[(char, char)] = &[
+    ('\u{1680}', '\u{169c}'),
+];
+
+pub(crate) const OL_CHIKI: &[(char, char)] = &[
+    ('\u{1c50}', '\u{1c7f}'),
+];
+
+pub(crate) const OL_ONAL: &[(char, char)] = &[
+    ('\u{1e5d0}', '\u{1e5fa}'), ('\u{1e5ff}', '\u{1e5ff}'),
+];
+
+pub(crate) const OLD_HUNGARIAN: &[(char, char)] = &[
+    ('\u{10c80}', '\u{10cb2}'), ('\u{10cc0}', '\u{10cf2}'), ('\u{10cfa}', '\u{10cff}'),
+];
+
+pub(crate) const OLD_ITALIC: &[(char, char)] = &[
+    ('\u{10300}', '\u{10323}'), ('\u{1032d}', '\u{1032f}'),
+];
+
+pub(crate) const OLD_NORTH_ARABIAN: &[(char, char)] = &[
+    ('\u{10a80}', '\u{10a9f}'),
+];
+
+pub(crate) const OLD_PERMIC: &[(char, char)] = &[
+    ('\u{10350}', '\u{1037a}'),
+];
+
+pub(crate) const OLD_PERSIAN: &[(char, char)] = &[
+    ('\u{103a0}', '\u{103c3}'), ('\u{103c8}', '\u{103d5}'),
+];
+
+pub(crate) const OLD_SOGDIAN: &[(char, char)] = &[
+    ('\u{10f00}', '\u{10f27}'),
+];
+
+pub(crate) const OLD_SOUTH_ARABIAN: &[(char, char)] = &[
+    ('\u{10a60}', '\u{10a7f}'),
+];
+
+pub(crate) const OLD_TURKIC: &[(char, char)] = &[
+    ('\u{10c00}', '\u{10c48}'),
+];
+
+pub(crate) const OLD_UYGHUR: &[(char, char)] = &[
+    ('\u{10f70}', '\u{10f89}'),
+];
+
+pub(crate) const ORIYA: &[(char, char)] = &[
+    ('\u{b01}', '\u{b03}'), ('\u{b05}', '\u{b0c}'), ('\u{b0f}', '\u{b10}'),
+    ('\u{b13}', '\u{b28}'), ('\u{b2a}', '\u{b30}'), ('\u{b32}', '\u{b33}'),
+    ('\u{b35}', '\u{b39}'), ('\u{b3c}', '\u{b44}'), ('\u{b47}', '\u{b48}'),
+    ('\u{b4b}', '\u{b4d}'), ('\u{b55}', '\u{b57}'), ('\u{b5c}', '\u{b5d}'),
+    ('\u{b5f}', '\u{b63}'), ('\u{b66}', '\u{b77}'),
+];
+
+pub(crate) const OSAGE: &[(char, char)] = &[
+    ('\u{104b0}', '\u{104d3}'), ('\u{104d8}', '\u{104fb}'),
+];
+
+pub(crate) const OSMANYA: &[(char, char)] = &[
+    ('\u{10480}', '\u{1049d}'), ('\u{104a0}', '\u{104a9}'),
+];
+
+pub(crate) const PAHAWH_HMONG: &[(char, char)] = &[
+    ('\u{16b00}', '\u{16b45}'), ('\u{16b50}', '\u{16b59}'), ('\u{16b5b}', '\u{16b61}'),
+    ('\u{16b63}', '\u{16b77}'), ('\u{16b7d}', '\u{16b8f}'),
+];
+
+pub(crate) const PALMYRENE: &[(char, char)] = &[
+    ('\u{10860}', '\u{1087f}'),
+];
+
+pub(crate) const PAU_CIN_HAU: &[(char, char)] = &[
+    ('\u{11ac0}', '\u{11af8}'),
+];
+
+pub(crate) const PHAGS_PA: &[(char, char)] = &[
+    ('\u{a840}', '\u{a877}'),
+];
+
+pub(crate) const PHOENICIAN: &[(char, char)] = &[
+    ('\u{10900}', '\u{1091b}'), ('\u{1091f}', '\u{1091f}'),
+];
+
+pub(crate) const PSALTER_PAHLAVI: &[(char, char)] = &[
+    ('\u{10b80}', '\u{10b91}'), ('\u{10b99}', '\u{10b9c}'), ('\u{10ba9}', '\u{10baf}'),
+];
+
+pub(crate) const REJANG: &[(char, char)] = &[
+    ('\u{a930}', '\u{a953}'), ('\u{a95f}', '\u{a95f}'),
+];
+
+pub(crate) const RUNIC: &[(char, char)] = &[
+    ('\u{16a0}', '\u{16ea}'), ('\u{16ee}', '\u{16f8}'),
+];
+
+pub(crate) const SAMARITAN: &[(char, char)] = &[
+    ('\u{800}', '\u{82d}'), ('\u{830}', '\u{83e}'),
+];
+
+pub(crate) const SAURASHTRA: &[(char, char)] = &[
+    ('\u{a880}', '\u{a8c5}'), ('\u{a8ce}', '\u{a8d9}'),
+];
+
+pub(crate) const SHARADA: &[(char, char)] = &[
+    ('\u{11180}', '\u{111df}'),
+];
+
+pub(crate) const SHAVIAN: &[(char, char)] = &[
+    ('\u{10450}', '\u{1047f}'),
+];
+
+pub(crate) const SIDDHAM: &[(char, char)] = &[
+    ('\u{11580}', '\u{115b5}'), ('\u{115b8}', '\u{115dd}'),
+];
+
+pub(crate) const SIGNWRITING: &[(char, char)] = &[
+    ('\u{1d800}', '\u{1da8b}'), ('\u{1da9b}', '\u{1da9f}'), ('\u{1daa1}', '\u{1daaf}'),
+];
+
+pub(crate) const SINHALA: &[(char, char)] = &[
+    ('\u{d81}', '\u{d83}'), ('\u{d85}', '\u{d96}'), ('\u{d9a}', '\u{db1}'),
+    ('\u{db3}', '\u{dbb}'), ('\u{dbd}', '\u{dbd}'), ('\u{dc0}', '\u{dc6}'),
+    ('\u{dca}', '\u{dca}'), ('\u{dcf}', '\u{dd4}'), ('\u{dd6}', '\u{dd6}'),
+    ('\u{dd8}', '\u{ddf}'), ('\u{de6}', '\u{def}'), ('\u{df2}', '\u{df4}'),
+    ('\u{111e1}', '\u{111f4}'),
+];
+
+pub(crate) const SOGDIAN: &[(char, char)] = &[
+    ('\u{10f30}', '\u{10f59}'),
+];
+
+pub(crate) const SORA_SOMPENG: &[(char, char)] = &[
+    ('\u{110d0}', '\u{110e8}'), ('\u{110f0}', '\u{110f9}'),
+];
+
+pub(crate) const SOYOMBO: &[(char, char)] = &[
+    ('\u{11a50}', '\u{11aa2}'),
+];
+
+pub(crate) const SUNDANESE: &[(char, char)] = &[
+    ('\u{1b80}', '\u{1bbf}'), ('\u{1cc0}', '\u{1cc7}'),
+];
+
+pub(crate) const SUNUWAR: &[(char, char)] = &[
+    ('\u{11bc0}', '\u{11be1}'), ('\u{11bf0}', '\u{11bf9}'),
+];
+
+pub(crate) const SYLOTI_NAGRI: &[(char, char)] = &[
+    ('\u{a800}', '\u{a82c}'),
+];
+
+pub(crate) const SYRIAC: &[(char, char)] = &[
+    ('\u{700}', '\u{70d}'), ('\u{70f}', '\u{74a}'), ('\u{74d}', '\u{74f}'),
+    ('\u{860}', '\u{86a}'),
+];
+
+pub(crate) const TAGALOG: &[(char, char)] = &[
+    ('\u{1700}', '\u{1715}'), ('\u{171f}', '\u{171f}'),
+];
+
+pub(crate) const TAGBANWA: &[(char, char)] = &[
+    ('\u{1760}', '\u{176c}'), ('\u{176e}', '\u{1770}'), ('\u{1772}', '\u{1773}'),
+];
+
+pub(crate) const TAI_LE: &[(char, char)] = &[
+    ('\u{1950}', '\u{196d}'), ('\u{1970}', '\u{1974}'),
+];
+
+pub(crate) const TAI_THAM: &[(char, char)] = &[
+    ('\u{1a20}', '\u{1a5e}'), ('\u{1a60}', '\u{1a7c}'), ('\u{1a7f}', '\u{1a89}'),
+    ('\u{1a90}', '\u{1a99}'), ('\u{1aa0}', '\u{1aad}'),
+];
+
+pub(crate) const TAI_VIET: &[(char, char)] = &[
+    ('\u{aa80}', '\u{aac2}'), ('\u{aadb}', '\u{aadf}'),
+];
+
+pub(crate) const TAKRI: &[(char, char)] = &[
+    ('\u{11680}', '\u{116b9}'), ('\u{116c0}', '\u{116c9}'),
+];
+
+pub(crate) const TAMIL: &[(char, char)] = &[
+    ('\u{b82}', '\u{b83}'), ('\u{b85}', '\u{b8a}'), ('\u{b8e}', '\u{b90}'),
+    ('\u{b92}', '\u{b95}'), ('\u{b99}', '\u{b9a}'), ('\u{b9c}', '\u{b9c}'),
+    ('\u{b9e}', '\u{b9f}'), ('\u{ba3}', '\u{ba4}'), ('\u{ba8}', '\u{baa}'),
+    ('\u{bae}', '\u{bb9}'), ('\u{bbe}', '\u{bc2}'), ('\u{bc6}', '\u{bc8}'),
+    ('\u{bca}', '\u{bcd}'), ('\u{bd0}', '\u{bd0}'), ('\u{bd7}', '\u{bd7}'),
+    ('\u{be6}', '\u{bfa}'), ('\u{11fc0}', '\u{11ff1}'), ('\u{11fff}', '\u{11fff}'),
+];
+
+pub(crate) const TANGSA: &[(char, char)] = &[
+    ('\u{16a70}', '\u{16abe}'), ('\u{16ac0}', '\u{16ac9}'),
+];
+
+pub(crate) const TANGUT: &[(char, char)] = &[
+    ('\u{16fe0}', '\u{16fe0}'), ('\u{17000}', '\u{187f7}'), ('\u{18800}', '\u{18aff}'),
+    ('\u{18d00}', '\u{18d08}'),
+];
+
+pub(crate) const TELUGU: &[(char, char)] = &[
+    ('\u{c00}', '\u{c0c}'), ('\u{c0e}', '\u{c10}'), ('\u{c12}', '\u{c28}'),
+    ('\u{c2a}', '\u{c39}'), ('\u{c3c}', '\u{c44}'), ('\u{c46}', '\u{c48}'),
+    ('\u{c4a}', '\u{c4d}'), ('\u{c55}', '\u{c56}'), ('\u{c58}', '\u{c5a}'),
+    ('\u{c5d}', '\u{c5d}'), ('\u{c60}', '\u{c63}'), ('\u{c66}', '\u{c6f}'),
+    ('\u{c77}', '\u{c7f}'),
+];
+
+pub(crate) const THAANA: &[(char, char)] = &[
+    ('\u{780}', '\u{7b1}'),
+];
+
+pub(crate) const THAI: &[(char, char)] = &[
+    ('\u{e01}', '\u{e3a}'), ('\u{e40}', '\u{e5b}'),
+];
+
+pub(crate) const TIBETAN: &[(char, char)] = &[
+    ('\u{f00}', '\u{f47}'), ('\u{f49}', '\u{f6c}'), ('\u{f71}', '\u{f97}'),
+    ('\u{f99}', '\u{fbc}'), ('\u{fbe}', '\u{fcc}'), ('\u{fce}', '\u{fd4}'),
+    ('\u{fd9}', '\u{fda}'),
+];
+
+pub(crate) const TIFINAGH: &[(char, char)] = &[
+    ('\u{2d30}', '\u{2d67}'), ('\u{2d6f}', '\u{2d70}'), ('\u{2d7f}', '\u{2d7f}'),
+];
+
+pub(crate) const TIRHUTA: &[(char, char)] = &[
+    ('\u{11480}', '\u{114c7}'), ('\u{114d0}', '\u{114d9}'),
+];
+
+pub(crate) const TODHRI: &[(char, char)] = &[
+    ('\u{105c0}', '\u{105f3}'),
+];
+
+pub(crate) const TOTO: &[(char, char)] = &[
+    ('\u{1e290}', '\u{1e2ae}'),
+];
+
+pub(crate) const TULU_TIGALARI: &[(char, char)] = &[
+    ('\u{11380}', '\u{11389}'), ('\u{1138b}', '\u{1138b}'), ('\u{1138e}', '\u{1138e}'),
+    ('\u{11390}', '\u{113b5}'), ('\u{113b7}', '\u{113c0}'), ('\u{113c2}', '\u{113c2}'),
+    ('\u{113c5}', '\u{113c5}'), ('\u{113c7}', '\u{113ca}'), ('\u{113cc}', '\u{113d5}'),
+    ('\u{113d7}', '\u{113d8}'), ('\u{113e1}', '\u{113e2}'),
+];
+
+pub(crate) const UGARITIC: &[(char, char)] = &[
+    ('\u{10380}', '\u{1039d}'), ('\u{1039f}', '\u{1039f}'),
+];
+
+pub(crate) const VAI: &[(char, char)] = &[
+    ('\u{a500}', '\u{a62b}'),
+];
+
+pub(crate) const VITHKUQI: &[(char, char)] = &[
+    ('\u{10570}', '\u{1057a}'), ('\u{1057c}', '\u{1058a}'), ('\u{1058c}', '\u{10592}'),
+    ('\u{10594}', '\u{10595}'), ('\u{10597}', '\u{105a1}'), ('\u{105a3}', '\u{105b1}'),
+    ('\u{105b3}', '\u{105b9}'), ('\u{105bb}', '\u{105bc}'),
+];
+
+pub(crate) const WANCHO: &[(char, char)] = &[
+    ('\u{1e2c0}', '\u{1e2f9}'), ('\u{1e2ff}', '\u{1e2ff}'),
+];
+
+pub(crate) const WARANG_CITI: &[(char, char)] = &[
+    ('\u{118a0}', '\u{118f2}'), ('\u{118ff}', '\u{118ff}'),
+];
+
+pub(crate) const YEZIDI: &[(char, char)] = &[
+    ('\u{10e80}', '\u{10ea9}'), ('\u{10eab}', '\u{10ead}'), ('\u{10eb0}', '\u{10eb1}'),
+];
+
+pub(crate) const YI: &[(char, char)] = &[
+    ('\u{a000}', '\u{a48c}'), ('\u{a490}', '\u{a4c6}'),
+];
+
+pub(crate) const ZANABAZAR_SQUARE: &[(char, char)] = &[
+    ('\u{11a00}', '\u{11a47}'),
+];